@@ -1,6 +1,9 @@
+use crate::renamer::{validate_format, validate_template};
+use hyprland::data::FullscreenMode;
 use regex::Regex;
 use semver::Version;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
@@ -54,6 +57,14 @@ fn default_workspace_formatter() -> String {
     "{id}:{delim}{clients}".to_string()
 }
 
+fn default_rename_empty_workspaces() -> bool {
+    true
+}
+
+fn default_dedup_same_state() -> bool {
+    true
+}
+
 fn default_class() -> HashMap<String, String> {
     HashMap::from([("DEFAULT".to_string(), " {class}".to_string())])
 }
@@ -65,7 +76,26 @@ impl Default for ConfigFormatRaw {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMarkup {
+    #[default]
+    Pango,
+    Plain,
+}
+
+/// What makes two clients the same for `dedup`.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupBy {
+    #[default]
+    Rule,
+    Icon,
+    Class,
+    InitialClass,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct ConfigFormatRaw {
     #[serde(default)]
     pub max_clients: Option<i32>,
@@ -73,12 +103,32 @@ pub struct ConfigFormatRaw {
     pub dedup: bool,
     #[serde(default)]
     pub dedup_inactive_fullscreen: bool,
+    #[serde(default)]
+    pub dedup_inactive_maximized: Option<bool>,
+    #[serde(default)]
+    pub dedup_by: DedupBy,
+    #[serde(default = "default_dedup_same_state")]
+    pub dedup_same_state: bool,
+    #[serde(default)]
+    pub output_markup: OutputMarkup,
+    #[serde(default)]
+    pub workspaces_per_monitor: Option<i32>,
+    #[serde(default = "default_rename_empty_workspaces")]
+    pub rename_empty_workspaces: bool,
     #[serde(default = "default_delim_formatter")]
     pub delim: String,
     #[serde(default = "default_workspace_formatter")]
     pub workspace: String,
     #[serde(default = "default_workspace_empty_formatter")]
     pub workspace_empty: String,
+    #[serde(default)]
+    pub workspace_active: Option<String>,
+    #[serde(default)]
+    pub workspace_visible: Option<String>,
+    #[serde(default)]
+    pub workspace_special: Option<String>,
+    #[serde(default)]
+    pub workspace_urgent: Option<String>,
     #[serde(default = "default_client_formatter")]
     pub client: String,
     #[serde(default = "default_client_fullscreen_formatter")]
@@ -91,12 +141,153 @@ pub struct ConfigFormatRaw {
     pub client_dup_active: String,
     #[serde(default = "default_client_dup_fullscreen_formatter")]
     pub client_dup_fullscreen: String,
+    #[serde(default)]
+    pub client_overflow: Option<String>,
+    #[serde(default)]
+    pub client_maximized: Option<String>,
+    #[serde(default)]
+    pub client_dup_maximized: Option<String>,
+    #[serde(default)]
+    pub client_urgent: Option<String>,
+    #[serde(default)]
+    pub client_hidden: Option<String>,
+    #[serde(default)]
+    pub client_swallowed: Option<String>,
+    #[serde(default)]
+    pub client_last_focused: Option<String>,
+    #[serde(default)]
+    pub client_grouped: Option<String>,
+    #[serde(default)]
+    pub client_pinned: Option<String>,
+    #[serde(default)]
+    pub client_floating: Option<String>,
+    #[serde(default)]
+    pub client_xwayland: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub monitor: HashMap<String, toml::Table>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub workspaces: HashMap<String, toml::Table>,
+}
+
+/// The formatters a `[client_format."class"]` section can override, as listed in the README.
+const CLIENT_FORMATTERS: [&str; 17] = [
+    "delim",
+    "client",
+    "client_active",
+    "client_fullscreen",
+    "client_dup",
+    "client_dup_active",
+    "client_dup_fullscreen",
+    "client_maximized",
+    "client_dup_maximized",
+    "client_urgent",
+    "client_hidden",
+    "client_swallowed",
+    "client_last_focused",
+    "client_grouped",
+    "client_pinned",
+    "client_floating",
+    "client_xwayland",
+];
+
+impl ConfigFormatRaw {
+    /// Whether the inactive clients in this fullscreen mode are formatted and deduplicated
+    /// like the others, `dedup_inactive_maximized` defaulting to `dedup_inactive_fullscreen`.
+    pub fn dedup_inactive(&self, mode: FullscreenMode) -> bool {
+        match mode {
+            FullscreenMode::Maximized => self
+                .dedup_inactive_maximized
+                .unwrap_or(self.dedup_inactive_fullscreen),
+            _ => self.dedup_inactive_fullscreen,
+        }
+    }
+
+    /// Returns a copy of this format with the keys of `overrides` replaced,
+    /// failing on a key which is not a `[format]` parameter.
+    pub fn with_overrides(&self, overrides: &toml::Table) -> Result<Self, Box<dyn Error>> {
+        let mut format = toml::Table::try_from(self)?;
+        format.extend(overrides.clone());
+        let format: Self = format.try_into()?;
+        // Unknown keys are dropped by serde, and the set ones are serialized back
+        let known = toml::Table::try_from(&format)?;
+        if let Some(key) = overrides.keys().find(|key| !known.contains_key(*key)) {
+            return Err(format!("{key} is not a format parameter").into());
+        }
+        Ok(format)
+    }
+
+    /// Returns the format of a workspace, overridden by `[format.monitor."<name>"]`
+    /// then by `[format.workspaces."<id>"]` (or `[format.workspace."<id>"]`).
+    pub fn for_workspace(&self, id: i32, monitor: &str) -> Result<Cow<'_, Self>, Box<dyn Error>> {
+        let mut format = Cow::Borrowed(self);
+        if let Some(overrides) = self.monitor.get(monitor) {
+            format = Cow::Owned(format.with_overrides(overrides)?);
+        }
+        if let Some(overrides) = self.workspaces.get(&id.to_string()) {
+            format = Cow::Owned(format.with_overrides(overrides)?);
+        }
+        Ok(format)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputKind {
+    HyprlandRename,
+    StdoutJson,
+    File,
+    Fifo,
+    Dbus,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputEncoding {
+    #[default]
+    Json,
+    Toml,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ConfigOutputRaw {
+    #[serde(rename = "type")]
+    pub kind: OutputKind,
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub encoding: OutputEncoding,
+    #[serde(default)]
+    pub format: toml::Table,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOutput {
+    pub kind: OutputKind,
+    pub path: Option<PathBuf>,
+    pub encoding: OutputEncoding,
+    pub format: ConfigFormatRaw,
+}
+
+/// The order of the clients in a workspace.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientSort {
+    #[default]
+    Position,
+    FocusHistory,
+    OpenOrder,
+    Class,
+    Icon,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct ConfigFileRaw {
     #[serde(default)]
     pub version: String,
+    #[serde(default)]
+    pub sort: ClientSort,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pin_first: Vec<String>,
     #[serde(default = "default_class", alias = "icons")]
     pub class: HashMap<String, String>,
     #[serde(default, alias = "active_icons", alias = "icons_active")]
@@ -105,8 +296,9 @@ pub struct ConfigFileRaw {
     pub initial_class: HashMap<String, String>,
     #[serde(default)]
     pub initial_class_active: HashMap<String, String>,
+    /// A table to keep the order of the keys, the first matching name regex wins.
     #[serde(default)]
-    pub workspaces_name: HashMap<String, String>,
+    pub workspaces_name: toml::Table,
     #[serde(default, alias = "title_icons")]
     pub title_in_class: HashMap<String, HashMap<String, String>>,
     #[serde(default, alias = "title_active_icons")]
@@ -127,13 +319,19 @@ pub struct ConfigFileRaw {
     pub exclude: HashMap<String, String>,
     #[serde(default)]
     pub format: ConfigFormatRaw,
+    #[serde(default)]
+    pub output: Vec<ConfigOutputRaw>,
+    #[serde(default)]
+    pub client_format: HashMap<String, toml::Table>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skip_workspaces: Vec<toml::Value>,
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct ConfigFile {
     pub class: Vec<(Regex, String)>,
     pub class_active: Vec<(Regex, String)>,
-    pub workspaces_name: Vec<(String, String)>,
+    pub workspaces_name: Vec<(WorkspaceKey, String)>,
     pub initial_class: Vec<(Regex, String)>,
     pub initial_class_active: Vec<(Regex, String)>,
     pub title_in_class: Vec<(Regex, Vec<(Regex, String)>)>,
@@ -146,6 +344,97 @@ pub struct ConfigFile {
     pub initial_title_in_initial_class_active: Vec<(Regex, Vec<(Regex, String)>)>,
     pub exclude: Vec<(Regex, Regex)>,
     pub format: ConfigFormatRaw,
+    pub output: Vec<ConfigOutput>,
+    pub client_format: Vec<(Regex, toml::Table)>,
+    pub skip_workspaces: Vec<WorkspaceKey>,
+    pub sort: ClientSort,
+    pub pin_first: Vec<Regex>,
+}
+
+/// A `[workspaces_name]` key: an id, a range of ids like `1..5`, or a glob or a regex on the workspace name.
+#[derive(Debug, Clone)]
+pub enum WorkspaceKey {
+    Id(i32),
+    Range(i32, i32),
+    Name(Regex),
+}
+
+impl WorkspaceKey {
+    pub fn parse(key: &str) -> Option<Self> {
+        if let Ok(id) = key.trim().parse() {
+            return Some(WorkspaceKey::Id(id));
+        }
+
+        let range = key
+            .split_once("..")
+            .and_then(|(start, end)| Some((start.trim().parse().ok()?, end.trim().parse().ok()?)));
+
+        let pattern = glob_to_regex(key).unwrap_or_else(|| key.to_string());
+        match range {
+            Some((start, end)) => Some(WorkspaceKey::Range(start, end)),
+            None => regex_with_error_logging(&format!("^(?:{pattern})$")).map(WorkspaceKey::Name),
+        }
+    }
+
+    pub fn matches(&self, id: i32, name: &str) -> bool {
+        match self {
+            WorkspaceKey::Id(key) => *key == id,
+            WorkspaceKey::Range(start, end) => (*start..=*end).contains(&id),
+            WorkspaceKey::Name(re) => re.is_match(name),
+        }
+    }
+
+    /// Exact ids first, then the narrowest ranges, then the names.
+    fn priority(&self) -> (u8, i64) {
+        match self {
+            WorkspaceKey::Id(_) => (0, 0),
+            WorkspaceKey::Range(start, end) => (1, *end as i64 - *start as i64),
+            WorkspaceKey::Name(_) => (2, 0),
+        }
+    }
+}
+
+/// The regex of a glob like `web-*` or `special:?`: a key with `*` or `?`
+/// and none of the other regex metacharacters.
+fn glob_to_regex(key: &str) -> Option<String> {
+    let is_glob = key.contains(['*', '?'])
+        && !key.contains(['.', '^', '$', '+', '(', ')', '[', ']', '{', '}', '|', '\\']);
+    is_glob.then(|| {
+        key.chars()
+            .map(|c| match c {
+                '*' => ".*".to_string(),
+                '?' => ".".to_string(),
+                c => regex::escape(&c.to_string()),
+            })
+            .collect()
+    })
+}
+
+impl std::fmt::Display for WorkspaceKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WorkspaceKey::Id(id) => write!(f, "{id}"),
+            WorkspaceKey::Range(start, end) => write!(f, "{start}..{end}"),
+            WorkspaceKey::Name(re) => {
+                let re = re.as_str();
+                write!(f, "{}", &re[4..re.len() - 2])
+            }
+        }
+    }
+}
+
+impl ConfigFile {
+    /// The output renaming workspaces in Hyprland, if any.
+    pub fn rename_output(&self) -> Option<&ConfigOutput> {
+        self.output
+            .iter()
+            .find(|output| output.kind == OutputKind::HyprlandRename)
+    }
+
+    /// Whether the workspace is in `skip_workspaces`, so never renamed.
+    pub fn is_skipped(&self, id: i32, name: &str) -> bool {
+        self.skip_workspaces.iter().any(|key| key.matches(id, name))
+    }
 }
 
 impl Config {
@@ -183,13 +472,16 @@ pub fn read_config_file(
     dump_config: bool,
     migrate_config: bool,
 ) -> Result<ConfigFile, Box<dyn Error>> {
-    let mut config: ConfigFileRaw = match &cfg_path {
-        Some(path) => {
-            let config_string = fs::read_to_string(path)?;
-            toml::from_str(&config_string).map_err(|e| format!("Unable to parse: {e:?}"))?
-        }
-        None => toml::from_str("").map_err(|e| format!("Unable to parse: {e:?}"))?,
+    let config_string = match &cfg_path {
+        Some(path) => fs::read_to_string(path)?,
+        None => String::new(),
     };
+    let mut config: toml::Table =
+        toml::from_str(&config_string).map_err(|e| format!("Unable to parse: {e:?}"))?;
+    alias_workspace_overrides(&mut config);
+    let mut config: ConfigFileRaw = config
+        .try_into()
+        .map_err(|e| format!("Unable to parse: {e:?}"))?;
 
     migrate_config_file(&mut config, migrate_config, cfg_path)?;
 
@@ -198,7 +490,7 @@ pub fn read_config_file(
         process::exit(0);
     }
 
-    Ok(ConfigFile {
+    let config = ConfigFile {
         class: generate_icon_config(&config.class),
         class_active: generate_icon_config(&config.class_active),
         workspaces_name: generate_workspaces_name_config(&config.workspaces_name),
@@ -217,8 +509,128 @@ pub fn read_config_file(
             &config.initial_title_in_initial_class_active,
         ),
         exclude: generate_exclude_config(&config.exclude),
+        output: generate_output_config(&config.output, &config.format)?,
+        client_format: generate_client_format_config(&config.client_format),
+        skip_workspaces: generate_skip_workspaces_config(&config.skip_workspaces),
+        sort: config.sort,
+        pin_first: config
+            .pin_first
+            .iter()
+            .filter_map(|class| regex_with_error_logging(class))
+            .collect(),
         format: config.format,
-    })
+    };
+
+    validate_config(&config)?;
+
+    Ok(config)
+}
+
+/// Reads `[format.workspace."<id>"]` as `[format.workspaces."<id>"]`, in `[format]`
+/// and in the `[[output]]` formats. TOML does not allow `workspace` to be both, so
+/// the workspace formatter then keeps its default.
+fn alias_workspace_overrides(config: &mut toml::Table) {
+    fn alias(format: &mut toml::Value) {
+        let Some(format) = format.as_table_mut() else {
+            return;
+        };
+        if !format.get("workspace").is_some_and(toml::Value::is_table) {
+            return;
+        }
+        if let Some(toml::Value::Table(overrides)) = format.remove("workspace") {
+            let workspaces = format
+                .entry("workspaces")
+                .or_insert_with(|| toml::Table::new().into());
+            if let Some(workspaces) = workspaces.as_table_mut() {
+                for (id, overrides) in overrides {
+                    workspaces.entry(id).or_insert(overrides);
+                }
+            }
+        }
+    }
+
+    if let Some(format) = config.get_mut("format") {
+        alias(format);
+    }
+    if let Some(outputs) = config.get_mut("output").and_then(toml::Value::as_array_mut) {
+        for output in outputs {
+            if let Some(format) = output.get_mut("format") {
+                alias(format);
+            }
+        }
+    }
+}
+
+/// Fails on invalid formatters or icons templates, instead of renaming with broken names.
+fn validate_config(config: &ConfigFile) -> Result<(), Box<dyn Error>> {
+    validate_format(&config.format)?;
+    for output in &config.output {
+        validate_format(&output.format).map_err(|e| format!("[[output]] {e}"))?;
+    }
+
+    for format in std::iter::once(&config.format).chain(config.output.iter().map(|o| &o.format)) {
+        let overrides = format
+            .monitor
+            .iter()
+            .map(|(name, o)| (format!("format.monitor.{name}"), o))
+            .chain(
+                format
+                    .workspaces
+                    .iter()
+                    .map(|(id, o)| (format!("format.workspaces.{id}"), o)),
+            );
+        for (section, overrides) in overrides {
+            validate_format(&format.with_overrides(overrides)?)
+                .map_err(|e| format!("{section}: {e}"))?;
+        }
+    }
+
+    let icons = [
+        &config.class,
+        &config.class_active,
+        &config.initial_class,
+        &config.initial_class_active,
+    ];
+    let title_icons = [
+        &config.title_in_class,
+        &config.title_in_class_active,
+        &config.title_in_initial_class,
+        &config.title_in_initial_class_active,
+        &config.initial_title_in_class,
+        &config.initial_title_in_class_active,
+        &config.initial_title_in_initial_class,
+        &config.initial_title_in_initial_class_active,
+    ];
+
+    icons
+        .into_iter()
+        .flatten()
+        .chain(
+            title_icons
+                .into_iter()
+                .flatten()
+                .flat_map(|(_, icons)| icons),
+        )
+        .try_for_each(|(rule, icon)| {
+            validate_template(icon).map_err(|e| format!("icon of {rule}: {e}"))
+        })?;
+
+    config.workspaces_name.iter().try_for_each(|(key, name)| {
+        validate_template(name).map_err(|e| format!("workspaces_name.{key}: {e}"))
+    })?;
+
+    for (rule, overrides) in &config.client_format {
+        if let Some(key) = overrides
+            .keys()
+            .find(|key| !CLIENT_FORMATTERS.contains(&key.as_str()))
+        {
+            return Err(format!("client_format.{rule}: {key} is not a client formatter").into());
+        }
+        validate_format(&config.format.with_overrides(overrides)?)
+            .map_err(|e| format!("client_format.{rule}: {e}"))?;
+    }
+
+    Ok(())
 }
 
 pub fn get_config_path(args: &Option<String>) -> Result<PathBuf, Box<dyn Error>> {
@@ -255,32 +667,89 @@ fn migrate_config_file(
 
 pub fn create_default_config(cfg_path: &PathBuf) -> Result<String, Box<dyn Error + 'static>> {
     // TODO: maybe we should dump the config from the default values of the struct?
-    let default_config = format!(r#"version = "{VERSION}"
+    let default_config = format!(
+        r#"version = "{VERSION}"
+
+# clients order: "position", "focus_history", "open_order", "class" or "icon"
+# sort = "position"
+# classes shown first
+# pin_first = ["(?i)firefox"]
+# workspaces never renamed, by id, range or regex on the name
+# skip_workspaces = [9, "20..25", "special:rec"]
 
 # [format]
 # Deduplicate icons if enable.
 # A superscripted counter will be added.
 # dedup = false
 # dedup_inactive_fullscreen = false # dedup more
+# dedup_inactive_maximized = false # defaults to dedup_inactive_fullscreen
+# what makes clients the same: "rule", "icon", "class" or "initial_class"
+# dedup_by = "rule"
+# false counts the active and fullscreen clients with the others
+# dedup_same_state = true
 # window delimiter
 # delim = " "
 # max_clients = 30 # you should not need this
+# appended when max_clients hides clients, with {{hidden_count}} and {{hidden_count_sup}}
+# client_overflow = "+{{hidden_count}}"
+# "pango" escapes {{title}} and {{class}}, "plain" removes the markup of formatters and icons
+# output_markup = "pango"
+# false leaves the empty workspaces untouched until they get a client, the focused one aside
+# rename_empty_workspaces = true
+# one range of ids per monitor (split-monitor-workspaces), for {{local_id}}
+# workspaces_per_monitor = 10
 
 # available formatter:
 # {{counter_sup}} - superscripted count of clients on the workspace, and simple {{counter}}, {{delim}}
-# {{icon}}, {{client}}
+# {{icon}}, {{client}}, {{title}}, {{class}} and {{title_raw}}, {{class_raw}} (not escaped)
+# filters: {{title|truncate(20, "…")|lower}}, {{class|upper}}, {{title|replace("a", "b")}}, {{id|pad(2)}},
+# {{id|sub(10)}}, number styles {{id|circled}}, {{counter|sub}}, sup, fullwidth, roman, words
+# conditions: {{?counter>1}}{{counter_sup}}{{/}}, {{?active}}*{{icon}}*{{:}}{{icon}}{{/}}, {{{{ and }}}} for literal braces
 # workspace formatter
 # workspace = "{{id}}:{{delim}}{{clients}}" # {{id}}, {{delim}} and {{clients}} are supported
 # workspace_empty = "{{id}}" # {{id}}, {{delim}} and {{clients}} are supported
+# also {{count}}, {{count_sup}}, {{unique_classes}}, {{monitor}}, {{monitor_id}}, {{active_title}},
+# {{last_window_title}}, {{is_active}}, {{is_visible}} and {{original_name}} (restored on exit)
+# {{monitor_short}}, {{local_id}} the id, or with workspaces_per_monitor = 10, 3 for 13
+# focused and visible workspaces, default to workspace_visible then workspace
+# workspace_active = "<b>{{id}}</b>:{{delim}}{{clients}}"
+# workspace_visible = "<i>{{id}}</i>:{{delim}}{{clients}}"
+# special workspaces, with {{special_name}}
+# workspace_special = "{{special_name}}:{{delim}}{{clients}}"
+# workspaces with a client asking for attention, with {{has_urgent}}
+# workspace_urgent = "{{id}}!:{{delim}}{{clients}}"
 # client formatter
 # client = "{{icon}}"
 # client_active = "*{{icon}}*"
+# clients asking for attention, until focused, {{urgent}} is true
+# swallowed and unmapped clients are only shown when set, with {{swallowed}} and {{hidden}}
+# client_swallowed = "({{client}})"
+# client_hidden = "({{client}})"
+# client_urgent = "!{{client}}"
+# the client focused when switching to an unfocused workspace, {{is_last_focused}} is true
+# client_last_focused = "<u>{{client}}</u>"
+# the first set one is used, with {{floating}}, {{pinned}}, {{grouped}}, {{xwayland}} and {{group_count}}
+# their {{client}} is the client as usually formatted, dup counter and active marker included
+# a tabbed group is shown once with client_grouped
+# client_grouped = "[{{icon}}{{group_count_sup}}]"
+# client_pinned = "^{{client}}"
+# client_floating = "<small>{{client}}</small>"
+# client_xwayland = "{{client}}"
 
 # deduplicate client formatter
 # client_fullscreen = "[{{icon}}]"
 # client_dup = "{{client}}{{counter_sup}}"
 # client_dup_fullscreen = "[{{icon}}]{{delim}}{{icon}}{{counter_unfocused}}"
 # client_dup_active = "*{{icon}}*{{delim}}{{icon}}{{counter_unfocused}}"
+# maximized clients, default to client_fullscreen and client_dup_fullscreen
+# client_maximized = "({{icon}})"
+# client_dup_maximized = "({{icon}}){{delim}}{{icon}}{{counter_unfocused}}"
+
+# Override any [format] parameter for a monitor, then for a workspace
+# [format.monitor."eDP-1"]
+# max_clients = 5
+# [format.workspaces."10"]
+# workspace = "chat"
 
 [class]
 # Add your icons mapping
@@ -306,6 +775,8 @@ DEFAULT = "*{{icon}}*"
 "(?i)neomutt" = "neomutt"
 # regex captures support is supported
 # "emerge: (.+?/.+?)-.*" = "{{match1}}"
+# named groups too, in class rules as well
+# "emerge: (?P<pkg>.+?/.+?)-.*" = "{{pkg}}"
 
 [title_in_class_active."(?i)firefox"]
 "(?i)twitch" = "<span color='purple'>{{icon}}</span>"
@@ -325,6 +796,25 @@ DEFAULT = "*{{icon}}*"
 # [initial_title_active."(?i)kitty"]
 # "zsh" = "*Zsh*"
 
+# Send workspaces names to several outputs, each one with its own format.
+# Without [[output]], workspaces are only renamed in Hyprland.
+# type can be "hyprland-rename", "stdout-json", "file", "fifo" or "dbus"
+# file and fifo default to a path in $XDG_RUNTIME_DIR/hyprland-autoname-workspaces/
+# and support encoding = "json" or "toml"
+# [[output]]
+# type = "hyprland-rename"
+# [output.format]
+# client_active = "*{{icon}}*"
+#
+# [[output]]
+# type = "file"
+# path = "/tmp/hyprland-autoname-workspaces.json"
+# encoding = "json"
+
+# Override the client formatters for some classes
+# [client_format."(?i)kitty"]
+# client = "{{icon}} {{title|truncate(15)}}"
+
 # Add your applications that need to be exclude
 # The key is the class, the value is the title.
 # You can put an empty title to exclude based on
@@ -337,6 +827,9 @@ aProgram = "^$" # will match null title for aProgram
 "[Ss]team" = "^(Friends List.*)?$" # will match Steam friends list plus all popups (empty titles)
 
 [workspaces_name]
+# "special:term" = "terminal"
+# "11..20" = "{{id|sub(10)}}" # ranges of ids, exact ids win
+dev" = "dev" # regex on the original workspace name, the first matching one wins|dev" = "dev" # regex on the original workspace name, the first matching one wins
 0 = "zero"
 1 = "one"
 2 = "two"
@@ -349,7 +842,8 @@ aProgram = "^$" # will match null title for aProgram
 9 = "nine"
 10 = "ten"
 
-"#);
+"#
+    );
 
     let mut config_file = File::create(cfg_path)?;
     write!(&mut config_file, "{}", default_config.trim())?;
@@ -476,22 +970,137 @@ fn generate_exclude_config(icons: &HashMap<String, String>) -> Vec<(Regex, Regex
         .collect()
 }
 
-/// Generates the workspaces id to name mapping
-fn generate_workspaces_name_config(
-    workspaces_name: &HashMap<String, String>,
-) -> Vec<(String, String)> {
+/// Generates the per class overrides of the client formatters
+fn generate_client_format_config(
+    client_format: &HashMap<String, toml::Table>,
+) -> Vec<(Regex, toml::Table)> {
+    client_format
+        .iter()
+        .filter_map(|(class, overrides)| {
+            regex_with_error_logging(class).map(|re| (re, overrides.clone()))
+        })
+        .collect()
+}
+
+/// Generates the workspaces id to name mapping, in matching order:
+/// ids, the narrowest ranges, then the name regexes in the order of the config file
+pub fn generate_workspaces_name_config(
+    workspaces_name: &toml::Table,
+) -> Vec<(WorkspaceKey, String)> {
+    let mut workspaces_name: Vec<_> = workspaces_name
+        .iter()
+        .filter_map(|(key, name)| match name.as_str() {
+            Some(name) => WorkspaceKey::parse(key).map(|key| (key, name.to_string())),
+            None => {
+                println!("Unable to parse workspaces_name entry: {key} = {name}");
+                None
+            }
+        })
+        .collect();
+    // A stable sort, so the regexes keep their order
+    workspaces_name.sort_by_key(|(key, _)| key.priority());
     workspaces_name
+}
+
+/// Generates the workspaces to skip, from ids, ranges, names or regexes
+fn generate_skip_workspaces_config(skip_workspaces: &[toml::Value]) -> Vec<WorkspaceKey> {
+    skip_workspaces
         .iter()
-        .filter_map(|(id, name)| {
-            if id.parse::<i32>().is_ok() {
-                Some((id.to_string(), name.to_string()))
-            } else {
+        .filter_map(|key| match key {
+            toml::Value::Integer(id) => Some(id.to_string()),
+            toml::Value::String(key) => Some(key.clone()),
+            _ => {
+                println!("Unable to parse skip_workspaces entry: {key}");
                 None
             }
         })
+        .filter_map(|key| WorkspaceKey::parse(&key))
+        .collect()
+}
+
+/// Generates the list of outputs, each one with its own format.
+///
+/// Without any `[[output]]`, workspaces are only renamed in Hyprland, as before.
+/// Outputs writing to a file or a fifo default to a path in `$XDG_RUNTIME_DIR`,
+/// a second `hyprland-rename` output would fight with the first one so it is ignored.
+fn generate_output_config(
+    outputs: &[ConfigOutputRaw],
+    format: &ConfigFormatRaw,
+) -> Result<Vec<ConfigOutput>, Box<dyn Error>> {
+    if outputs.is_empty() {
+        return Ok(vec![ConfigOutput {
+            kind: OutputKind::HyprlandRename,
+            path: None,
+            encoding: OutputEncoding::Json,
+            format: format.clone(),
+        }]);
+    }
+
+    let mut has_rename_output = false;
+    outputs
+        .iter()
+        .filter_map(|output| {
+            match output.kind {
+                OutputKind::HyprlandRename if has_rename_output => {
+                    println!("Only one hyprland-rename output is supported, ignoring the others");
+                    return None;
+                }
+                OutputKind::HyprlandRename => has_rename_output = true,
+                OutputKind::Dbus if !cfg!(feature = "dbus") => {
+                    println!("Built without D-Bus support, ignoring the dbus output");
+                    return None;
+                }
+                _ => {}
+            };
+
+            let path = match (output.kind, &output.path) {
+                (OutputKind::File | OutputKind::Fifo, None) => {
+                    match default_output_path(output.kind, output.encoding) {
+                        Ok(path) => Some(path),
+                        Err(e) => {
+                            println!("Output {:?} need a path, ignoring it: {e:?}", output.kind);
+                            return None;
+                        }
+                    }
+                }
+                (_, path) => path.clone(),
+            };
+
+            Some(
+                format
+                    .with_overrides(&output.format)
+                    .map(|output_format| ConfigOutput {
+                        kind: output.kind,
+                        path,
+                        encoding: output.encoding,
+                        format: output_format,
+                    })
+                    .map_err(|e| format!("[[output]] format: {e}").into()),
+            )
+        })
         .collect()
 }
 
+/// Returns the default path of a file or fifo output,
+/// in `$XDG_RUNTIME_DIR/hyprland-autoname-workspaces/`.
+fn default_output_path(
+    kind: OutputKind,
+    encoding: OutputEncoding,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let file_name = match (kind, encoding) {
+        (OutputKind::Fifo, _) => "state.fifo",
+        (_, OutputEncoding::Json) => "state.json",
+        (_, OutputEncoding::Toml) => "state.toml",
+    };
+
+    // Only resolved here, the directory is created when the output is written
+    let xdg_dirs = xdg::BaseDirectories::new()?;
+    Ok(xdg_dirs
+        .get_runtime_directory()?
+        .join(BIN_NAME)
+        .join(file_name))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -537,6 +1146,195 @@ mod tests {
         assert!(exclude_config[0].1.is_match("Title1"));
     }
 
+    #[test]
+    fn test_skip_workspaces() {
+        let raw: ConfigFileRaw =
+            toml::from_str(r#"skip_workspaces = [9, "20..25", "special:rec", "rec-.*", true]"#)
+                .unwrap();
+        let config = ConfigFile {
+            skip_workspaces: generate_skip_workspaces_config(&raw.skip_workspaces),
+            ..ConfigFile::default()
+        };
+
+        assert_eq!(config.skip_workspaces.len(), 4);
+        assert!(config.is_skipped(9, "9"));
+        assert!(config.is_skipped(21, "21"));
+        assert!(config.is_skipped(-98, "special:rec"));
+        assert!(config.is_skipped(3, "rec-1"));
+        assert!(!config.is_skipped(3, "3"));
+        assert!(!config.is_skipped(-97, "special:recording"));
+    }
+
+    #[test]
+    fn test_workspace_key_globs() {
+        let key = |key: &str| WorkspaceKey::parse(key).unwrap();
+        assert!(key("web-*").matches(1, "web-mail"));
+        assert!(!key("web-*").matches(1, "webmail"));
+        assert!(key("special:?").matches(-98, "special:a"));
+        assert!(!key("special:?").matches(-98, "special:ab"));
+        // With other metacharacters, the key is a regex
+        assert!(key("web.*").matches(1, "webmail"));
+        assert!(key("(mail|chat)-?").matches(1, "mail"));
+    }
+
+    #[test]
+    fn test_read_workspaces_name() {
+        let cfg_path = PathBuf::from("/tmp/hyprland-autoname-workspaces-test-names.toml");
+        fs::write(
+            &cfg_path,
+            r#"
+            [workspaces_name]
+            "11..20" = "{id|sub(10)}"
+            "web-*" = "web"
+            "#,
+        )
+        .unwrap();
+        let config = read_config_file(Some(cfg_path.clone()), false, false).unwrap();
+        fs::remove_file(&cfg_path).unwrap();
+
+        let keys: Vec<String> = config
+            .workspaces_name
+            .iter()
+            .map(|(key, name)| format!("{key} = {name}"))
+            .collect();
+        assert_eq!(keys, ["11..20 = {id|sub(10)}", "web\\-.* = web"]);
+    }
+
+    #[test]
+    fn test_generate_workspaces_name_config() {
+        let workspaces_name = generate_workspaces_name_config(&toml::toml! {
+            "web|code" = "w"
+            "1..10" = "a"
+            "code" = "c"
+            "2..3" = "b"
+            "4" = "d"
+            "(" = "invalid"
+            "5" = 5
+        });
+
+        let keys: Vec<String> = workspaces_name
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .collect();
+        assert_eq!(keys, ["4", "2..3", "1..10", "web|code", "code"]);
+    }
+
+    #[test]
+    fn test_generate_output_config() {
+        let format = ConfigFormatRaw::default();
+
+        let outputs = generate_output_config(&[], &format).unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].kind, OutputKind::HyprlandRename);
+        assert_eq!(outputs[0].format, format);
+
+        let config: ConfigFileRaw = toml::from_str(
+            r#"
+            [[output]]
+            type = "hyprland-rename"
+            format.client_active = "{icon}"
+
+            [[output]]
+            type = "hyprland-rename"
+
+            [[output]]
+            type = "stdout-json"
+            [output.format]
+            client_active = "<b>{icon}</b>"
+            dedup = true
+
+            [[output]]
+            type = "fifo"
+            path = "/tmp/state.fifo"
+            "#,
+        )
+        .unwrap();
+
+        let outputs = generate_output_config(&config.output, &config.format).unwrap();
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs[0].kind, OutputKind::HyprlandRename);
+        assert_eq!(outputs[0].format.client_active, "{icon}");
+        assert_eq!(outputs[1].kind, OutputKind::StdoutJson);
+        assert_eq!(outputs[1].format.client_active, "<b>{icon}</b>");
+        assert!(outputs[1].format.dedup);
+        assert_eq!(outputs[1].format.client, format.client);
+        assert_eq!(outputs[2].path, Some(PathBuf::from("/tmp/state.fifo")));
+        assert_eq!(outputs[2].encoding, OutputEncoding::Json);
+
+        let config: ConfigFileRaw = toml::from_str(
+            r#"
+            [[output]]
+            type = "stdout-json"
+            format.worksapce_empty = "{id}"
+            "#,
+        )
+        .unwrap();
+        assert!(generate_output_config(&config.output, &config.format).is_err());
+    }
+
+    #[test]
+    fn test_format_overrides() {
+        let mut config: toml::Table = toml::from_str(
+            r#"
+            [format.monitor."eDP-1"]
+            max_clients = 2
+            [format.workspace."10"]
+            workspace = "chat"
+            [format.workspaces."11"]
+            dedup = true
+            "#,
+        )
+        .unwrap();
+        alias_workspace_overrides(&mut config);
+        let config: ConfigFileRaw = config.try_into().unwrap();
+        let mut ids: Vec<&String> = config.format.workspaces.keys().collect();
+        ids.sort();
+        assert_eq!(ids, ["10", "11"]);
+        assert_eq!(config.format.workspace, default_workspace_formatter());
+        assert_eq!(
+            config.format.for_workspace(10, "eDP-1").unwrap().workspace,
+            "chat"
+        );
+        assert_eq!(
+            config
+                .format
+                .for_workspace(10, "eDP-1")
+                .unwrap()
+                .max_clients,
+            Some(2)
+        );
+
+        let mut config = read_config_file(None, false, false).unwrap();
+        config.format.workspaces =
+            HashMap::from([("10".to_string(), toml::toml! { worksapce_empty = "{id}" })]);
+        assert!(validate_config(&config).is_err());
+        config.format.workspaces.clear();
+        config.format.monitor = HashMap::from([(
+            "eDP-1".to_string(),
+            toml::toml! { client_maximized = "({icon})" },
+        )]);
+        assert!(validate_config(&config).is_ok());
+        config.format.monitor =
+            HashMap::from([("eDP-1".to_string(), toml::toml! { max_client = 2 })]);
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_validate_client_format() {
+        let mut config = read_config_file(None, false, false).unwrap();
+        config.client_format = generate_client_format_config(&HashMap::from([(
+            "kitty".to_string(),
+            toml::toml! { client = "{icon} {title}" },
+        )]));
+        assert!(validate_config(&config).is_ok());
+
+        config.client_format[0].1 = toml::toml! { workspace = "{id}" };
+        assert!(validate_config(&config).is_err());
+
+        config.client_format[0].1 = toml::toml! { client = "{title" };
+        assert!(validate_config(&config).is_err());
+    }
+
     #[test]
     fn test_regex_with_error_logging() {
         let valid_pattern = "Class1";
@@ -550,7 +1348,7 @@ mod tests {
     fn test_config_new_and_read_again_then_compare_format() {
         let cfg_path = PathBuf::from("/tmp/hyprland-autoname-workspaces-test.toml");
         let config = Config::new(cfg_path.clone(), false, false);
-        assert_eq!(config.is_ok(), true);
+        assert!(config.is_ok());
         let config = config.unwrap().clone();
         assert_eq!(config.cfg_path.clone(), Some(cfg_path.clone()));
         let format = config.config.format.clone();
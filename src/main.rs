@@ -26,6 +26,14 @@ fn main() {
 
     // Init
     let renamer = Renamer::new(cfg.clone(), args);
+    if let Err(e) = renamer.load_default_names() {
+        eprintln!("Unable to read the workspace rules: {e}");
+    }
+
+    #[cfg(feature = "dbus")]
+    if let Err(e) = renamer.update_dbus() {
+        eprintln!("Unable to start the D-Bus service: {e:?}");
+    }
     renamer
         .rename_workspace()
         .expect("App can't rename workspaces on start");
@@ -36,7 +44,7 @@ fn main() {
 
     thread::spawn(move || {
         if signals.forever().next().is_some() {
-            match final_renamer.reset_workspaces(cfg.config) {
+            match final_renamer.reset_workspaces() {
                 Err(_) => println!("Workspaces name can't be cleared"),
                 Ok(_) => println!("Workspaces name cleared, bye"),
             };
@@ -0,0 +1,318 @@
+use crate::config::OutputKind;
+use crate::renamer::output::State;
+use crate::renamer::Renamer;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use zbus::blocking::ConnectionBuilder;
+use zbus::{block_on, dbus_interface, fdo, SignalContext};
+
+const DBUS_NAME: &str = "org.hyprland.AutonameWorkspaces";
+const DBUS_PATH: &str = "/org/hyprland/AutonameWorkspaces";
+
+type Names = HashMap<i32, String>;
+type Clients = HashMap<i32, Vec<(String, String, bool)>>;
+
+/// The methods renaming the workspaces, handled out of the D-Bus call.
+enum Request {
+    Reload,
+    Resume,
+}
+
+struct AutonameWorkspaces {
+    renamer: Arc<Renamer>,
+    requests: Mutex<Sender<Request>>,
+}
+
+#[dbus_interface(name = "org.hyprland.AutonameWorkspaces")]
+impl AutonameWorkspaces {
+    /// The computed name of each workspace, by id.
+    #[dbus_interface(property)]
+    fn names(&self) -> fdo::Result<Names> {
+        self.renamer
+            .dbus_state
+            .lock()
+            .map(|state| names(&state))
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// The clients (class, title, is_active) of each workspace, by id.
+    #[dbus_interface(property)]
+    fn clients(&self) -> fdo::Result<Clients> {
+        self.renamer
+            .dbus_state
+            .lock()
+            .map(|state| clients(&state))
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    fn reload(&self) -> fdo::Result<()> {
+        self.request(Request::Reload)
+    }
+
+    fn pause(&self) {
+        self.renamer.pause();
+    }
+
+    fn resume(&self) -> fdo::Result<()> {
+        self.request(Request::Resume)
+    }
+
+    #[dbus_interface(signal)]
+    async fn workspaces_changed(ctxt: &SignalContext<'_>, names: Names) -> zbus::Result<()>;
+}
+
+impl AutonameWorkspaces {
+    /// Queues the request: renaming publishes on D-Bus, which can't be done during a call.
+    fn request(&self, request: Request) -> fdo::Result<()> {
+        self.requests
+            .lock()
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?
+            .send(request)
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+}
+
+fn names(state: &State) -> Names {
+    state
+        .workspaces
+        .iter()
+        .map(|workspace| (workspace.id, workspace.name.clone()))
+        .collect()
+}
+
+fn clients(state: &State) -> Clients {
+    state
+        .workspaces
+        .iter()
+        .map(|workspace| {
+            let clients = workspace
+                .clients
+                .iter()
+                .map(|c| (c.class.clone(), c.title.clone(), c.is_active))
+                .collect();
+            (workspace.id, clients)
+        })
+        .collect()
+}
+
+impl Renamer {
+    /// Stops renaming workspaces until `resume` is called.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Renames again all workspaces, even the ones renamed by someone else during the pause.
+    pub fn resume(&self) -> Result<(), Box<dyn Error + '_>> {
+        self.paused.store(false, Ordering::Relaxed);
+        self.workspace_strings_cache.lock()?.clear();
+        self.output_cache.lock()?.clear();
+        self.rename_workspace()
+    }
+
+    /// Starts the D-Bus service on the session bus when there is a dbus output,
+    /// and stops it when there is none anymore.
+    pub fn update_dbus(self: &Arc<Self>) -> Result<(), Box<dyn Error + '_>> {
+        let has_dbus_output = self
+            .cfg
+            .lock()?
+            .config
+            .output
+            .iter()
+            .any(|output| output.kind == OutputKind::Dbus);
+        let is_started = self.dbus.lock()?.is_some();
+
+        match (has_dbus_output, is_started) {
+            (true, false) => self.serve_dbus(ConnectionBuilder::session()?),
+            (false, true) => self.stop_dbus(),
+            _ => Ok(()),
+        }
+    }
+
+    fn serve_dbus(self: &Arc<Self>, builder: ConnectionBuilder) -> Result<(), Box<dyn Error + '_>> {
+        let (requests, receiver) = channel();
+        let iface = AutonameWorkspaces {
+            renamer: self.clone(),
+            requests: Mutex::new(requests),
+        };
+
+        let connection = builder
+            .name(DBUS_NAME)?
+            .serve_at(DBUS_PATH, iface)?
+            .build()?;
+
+        *self.dbus.lock()? = Some(connection);
+
+        // Ends when the interface, and so the sender, is dropped
+        let renamer = self.clone();
+        thread::spawn(move || {
+            for request in receiver {
+                let result = match request {
+                    Request::Reload => renamer
+                        .reload_config()
+                        .and_then(|_| renamer.rename_workspace()),
+                    Request::Resume => renamer.resume(),
+                };
+                if let Err(e) = result {
+                    println!("Unable to handle D-Bus request: {e:?}");
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn stop_dbus(&self) -> Result<(), Box<dyn Error + '_>> {
+        if let Some(connection) = self.dbus.lock()?.take() {
+            connection
+                .object_server()
+                .remove::<AutonameWorkspaces, _>(DBUS_PATH)?;
+            connection.release_name(DBUS_NAME)?;
+        }
+
+        Ok(())
+    }
+
+    /// Updates the D-Bus properties and emits `WorkspacesChanged`.
+    pub fn publish_dbus(&self, state: &State) -> Result<(), Box<dyn Error + '_>> {
+        let Some(connection) = self.dbus.lock()?.clone() else {
+            return Ok(());
+        };
+
+        let iface_ref = connection
+            .object_server()
+            .interface::<_, AutonameWorkspaces>(DBUS_PATH)?;
+        let ctxt = iface_ref.signal_context();
+
+        *self.dbus_state.lock()? = state.clone();
+
+        {
+            let iface = iface_ref.get();
+            block_on(iface.names_changed(ctxt))?;
+            block_on(iface.clients_changed(ctxt))?;
+        }
+
+        block_on(AutonameWorkspaces::workspaces_changed(ctxt, names(state)))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigOutput, OutputEncoding};
+    use crate::renamer::output::{ClientState, WorkspaceState};
+    use crate::renamer::tests::test_renamer;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use zbus::blocking::{fdo::DBusProxy, Proxy};
+
+    #[test]
+    fn test_dbus_service() {
+        // Private bus, so the test does not need nor touch the user session
+        let mut dbus_daemon = match Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => {
+                println!("dbus-daemon not found, skipping");
+                return;
+            }
+        };
+        let mut address = String::new();
+        BufReader::new(dbus_daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim();
+
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        let output = ConfigOutput {
+            kind: OutputKind::Dbus,
+            path: None,
+            encoding: OutputEncoding::Json,
+            format: config.format.clone(),
+        };
+        config.output = vec![output.clone()];
+
+        let renamer = test_renamer(&config);
+        renamer
+            .serve_dbus(ConnectionBuilder::address(address).unwrap())
+            .unwrap();
+
+        let client = ConnectionBuilder::address(address)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(DBusProxy::new(&client)
+            .unwrap()
+            .name_has_owner(DBUS_NAME.try_into().unwrap())
+            .unwrap());
+
+        let proxy: Proxy = zbus::blocking::ProxyBuilder::new_bare(&client)
+            .destination(DBUS_NAME)
+            .unwrap()
+            .path(DBUS_PATH)
+            .unwrap()
+            .interface(DBUS_NAME)
+            .unwrap()
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .unwrap();
+        let mut signals = proxy.receive_signal("WorkspacesChanged").unwrap();
+
+        let state = State {
+            workspaces: vec![WorkspaceState {
+                id: 1,
+                name: "1: term".to_string(),
+                clients: vec![ClientState {
+                    class: "kitty".to_string(),
+                    title: "zsh".to_string(),
+                    is_active: true,
+                }],
+            }],
+        };
+        renamer.write_output(0, &output, &state).unwrap();
+
+        let signal = signals.next().unwrap();
+        let names: Names = signal.body().unwrap();
+        assert_eq!(names, HashMap::from([(1, "1: term".to_string())]));
+
+        let names: Names = proxy.get_property("Names").unwrap();
+        assert_eq!(names, HashMap::from([(1, "1: term".to_string())]));
+        let clients: Clients = proxy.get_property("Clients").unwrap();
+        assert_eq!(
+            clients,
+            HashMap::from([(1, vec![("kitty".to_string(), "zsh".to_string(), true)])])
+        );
+
+        let _: () = proxy.call("Pause", &()).unwrap();
+        assert!(renamer.paused.load(Ordering::Relaxed));
+
+        // Paused, the reload does not rename anything
+        let _: () = proxy.call("Reload", &()).unwrap();
+        assert!(renamer.paused.load(Ordering::Relaxed));
+
+        // Returns before renaming, which happens out of the call
+        let _: () = proxy.call("Resume", &()).unwrap();
+        let start = std::time::Instant::now();
+        while renamer.paused.load(Ordering::Relaxed) {
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        renamer.stop_dbus().unwrap();
+        assert!(!DBusProxy::new(&client)
+            .unwrap()
+            .name_has_owner(DBUS_NAME.try_into().unwrap())
+            .unwrap());
+
+        dbus_daemon.kill().unwrap();
+        dbus_daemon.wait().unwrap();
+    }
+}
@@ -0,0 +1,468 @@
+use crate::config::OutputMarkup;
+use crate::renamer::formatter::{escape_markup, unescape_markup};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const DEFAULT_ELLIPSIS: &str = "…";
+
+/// A filter applied to a placeholder value, like `truncate(20, "…")` in `{title|truncate(20, "…")}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// A piece of a value, the markup tags are never altered by the filters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Tag(String),
+    Text(String),
+}
+
+impl Filter {
+    pub fn apply(&self, value: &str, markup: OutputMarkup) -> Result<String, String> {
+        let mut tokens = tokenize(value, markup);
+
+        match (self.name.as_str(), self.args.as_slice()) {
+            ("lower", []) => map_text(&mut tokens, |t| t.to_lowercase()),
+            ("upper", []) => map_text(&mut tokens, |t| t.to_uppercase()),
+            ("trim", []) => map_text(&mut tokens, |t| t.trim().to_string()),
+            ("replace", [from, to]) => map_text(&mut tokens, |t| t.replace(from, to)),
+            ("truncate", [width]) => {
+                truncate(&mut tokens, self.usize_arg(width)?, DEFAULT_ELLIPSIS)
+            }
+            ("truncate", [width, ellipsis]) => {
+                truncate(&mut tokens, self.usize_arg(width)?, ellipsis)
+            }
+            ("pad", [width]) => pad(&mut tokens, self.usize_arg(width)?, None),
+            ("pad", [width, fill]) => pad(&mut tokens, self.usize_arg(width)?, Some(fill)),
+            ("add", [n]) => offset(&mut tokens, self.int_arg(n)?),
+            // With an argument `sub` subtracts, without it is the subscript style
+            ("sub" | "minus", [n]) => {
+                let negated = self.int_arg(n)?.checked_neg();
+                offset(
+                    &mut tokens,
+                    negated.ok_or_else(|| format!("{self}: '{n}' is out of range"))?,
+                )
+            }
+            ("sup", []) => map_number(&mut tokens, superscript),
+            ("sub", []) => map_number(&mut tokens, subscript),
+            ("circled", []) => map_number(&mut tokens, circled),
+            ("fullwidth", []) => map_number(&mut tokens, fullwidth),
+            ("roman", []) => map_number(&mut tokens, roman),
+            ("words", []) => map_number(&mut tokens, words),
+            (_, _) => return Err(format!("unknown filter {self}")),
+        };
+
+        Ok(detokenize(tokens, markup))
+    }
+
+    fn usize_arg(&self, arg: &str) -> Result<usize, String> {
+        arg.trim()
+            .parse()
+            .map_err(|_| format!("{self}: '{arg}' is not a positive number"))
+    }
+
+    fn int_arg(&self, arg: &str) -> Result<i64, String> {
+        arg.trim()
+            .parse()
+            .map_err(|_| format!("{self}: '{arg}' is not a number"))
+    }
+}
+
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}({})", self.name, self.args.join(", "))
+    }
+}
+
+/// Splits a value in tags and unescaped text when it holds Pango markup.
+fn tokenize(value: &str, markup: OutputMarkup) -> Vec<Token> {
+    if markup == OutputMarkup::Plain {
+        return vec![Token::Text(value.to_string())];
+    }
+
+    let mut tokens = vec![];
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let is_tag = rest[start + 1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c == '/' || c.is_ascii_alphabetic());
+        let end = rest[start..].find('>').map(|end| start + end + 1);
+
+        match end {
+            Some(end) if is_tag => {
+                tokens.push(Token::Text(unescape_markup(&rest[..start])));
+                tokens.push(Token::Tag(rest[start..end].to_string()));
+                rest = &rest[end..];
+            }
+            _ => {
+                tokens.push(Token::Text(unescape_markup(&rest[..start + 1])));
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    tokens.push(Token::Text(unescape_markup(rest)));
+
+    tokens
+}
+
+fn detokenize(tokens: Vec<Token>, markup: OutputMarkup) -> String {
+    tokens
+        .into_iter()
+        .map(|token| match (token, markup) {
+            (Token::Tag(tag), _) => tag,
+            (Token::Text(text), OutputMarkup::Pango) => escape_markup(&text),
+            (Token::Text(text), OutputMarkup::Plain) => text,
+        })
+        .collect()
+}
+
+fn map_text(tokens: &mut [Token], f: impl Fn(&str) -> String) {
+    for token in tokens.iter_mut() {
+        if let Token::Text(text) = token {
+            *text = f(text);
+        }
+    }
+}
+
+fn text_width(tokens: &[Token]) -> usize {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Text(text) => text.width(),
+            Token::Tag(_) => 0,
+        })
+        .sum()
+}
+
+/// Cuts the text to `width` columns, ellipsis included, without splitting graphemes.
+/// Tags are kept, so a `<span>` is still closed after the cut.
+fn truncate(tokens: &mut [Token], width: usize, ellipsis: &str) {
+    if text_width(tokens) <= width {
+        return;
+    }
+
+    let mut budget = width.saturating_sub(ellipsis.width());
+    let mut ellipsis = Some(ellipsis);
+    for token in tokens.iter_mut() {
+        if let Token::Text(text) = token {
+            let mut kept = String::new();
+            for grapheme in text.graphemes(true) {
+                let grapheme_width = grapheme.width();
+                if grapheme_width > budget {
+                    budget = 0;
+                    break;
+                }
+                budget -= grapheme_width;
+                kept.push_str(grapheme);
+            }
+
+            if budget == 0 {
+                if let Some(ellipsis) = ellipsis.take() {
+                    kept.push_str(ellipsis);
+                }
+            }
+            *text = kept;
+        }
+    }
+}
+
+/// Pads on the left to `width` columns, with zeros for numbers and spaces otherwise.
+fn pad(tokens: &mut Vec<Token>, width: usize, fill: Option<&str>) {
+    let missing = width.saturating_sub(text_width(tokens));
+    if missing == 0 {
+        return;
+    }
+
+    let text: String = tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(text.as_str()),
+            Token::Tag(_) => None,
+        })
+        .collect();
+    let is_number = text.parse::<i64>().is_ok();
+    let fill = fill.unwrap_or(if is_number { "0" } else { " " });
+    let padding = fill.repeat(missing / fill.width().max(1));
+
+    match tokens
+        .iter_mut()
+        .find(|token| matches!(token, Token::Text(t) if !t.is_empty()))
+    {
+        // Keep the sign in front of the zeros
+        Some(Token::Text(text)) if is_number && fill == "0" && text.starts_with('-') => {
+            text.insert_str(1, &padding);
+        }
+        Some(Token::Text(text)) => text.insert_str(0, &padding),
+        _ => tokens.insert(0, Token::Text(padding)),
+    }
+}
+
+/// Rewrites the numbers, other values are kept as is.
+fn map_number(tokens: &mut [Token], f: impl Fn(i64) -> String) {
+    map_text(tokens, |t| match t.trim().parse::<i64>() {
+        Ok(value) => f(value),
+        Err(_) => t.to_string(),
+    })
+}
+
+/// Adds `n` to a number.
+fn offset(tokens: &mut [Token], n: i64) {
+    map_number(tokens, |value| value.saturating_add(n).to_string())
+}
+
+const SUPERSCRIPT: [&str; 10] = ["⁰", "¹", "²", "³", "⁴", "⁵", "⁶", "⁷", "⁸", "⁹"];
+const SUBSCRIPT: [&str; 10] = ["₀", "₁", "₂", "₃", "₄", "₅", "₆", "₇", "₈", "₉"];
+const FULLWIDTH: [&str; 10] = ["０", "１", "２", "３", "４", "５", "６", "７", "８", "９"];
+
+/// Writes the number with other digits, and `minus` for the sign.
+fn map_digits(n: i64, digits: &[&str; 10], minus: &str) -> String {
+    let sign = if n < 0 { minus } else { "" };
+    let digits = n
+        .unsigned_abs()
+        .to_string()
+        .bytes()
+        .map(|digit| digits[(digit - b'0') as usize])
+        .collect::<String>();
+    format!("{sign}{digits}")
+}
+
+pub fn superscript(n: i64) -> String {
+    map_digits(n, &SUPERSCRIPT, "⁻")
+}
+
+fn subscript(n: i64) -> String {
+    map_digits(n, &SUBSCRIPT, "₋")
+}
+
+fn fullwidth(n: i64) -> String {
+    map_digits(n, &FULLWIDTH, "－")
+}
+
+/// `⑫` for `12`, up to 50, the bigger numbers are kept as is.
+fn circled(n: i64) -> String {
+    let circled = match n.unsigned_abs() {
+        0 => Some('⓪'),
+        abs @ 1..=20 => char::from_u32(0x2460 + abs as u32 - 1),
+        abs @ 21..=35 => char::from_u32(0x3251 + abs as u32 - 21),
+        abs @ 36..=50 => char::from_u32(0x32B1 + abs as u32 - 36),
+        _ => None,
+    };
+
+    match circled {
+        Some(c) if n < 0 => format!("-{c}"),
+        Some(c) => c.to_string(),
+        None => n.to_string(),
+    }
+}
+
+/// `XIV` for `14`, from 1 to 3999, other numbers are kept as is.
+fn roman(n: i64) -> String {
+    const NUMERALS: [(u64, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+
+    let mut rest = n.unsigned_abs();
+    if !(1..=3999).contains(&rest) {
+        return n.to_string();
+    }
+
+    let mut roman = String::from(if n < 0 { "-" } else { "" });
+    for (value, numeral) in NUMERALS {
+        while rest >= value {
+            roman.push_str(numeral);
+            rest -= value;
+        }
+    }
+    roman
+}
+
+/// `minus twenty-one` for `-21`.
+fn words(n: i64) -> String {
+    if n < 0 {
+        format!("minus {}", unsigned_words(n.unsigned_abs()))
+    } else {
+        unsigned_words(n.unsigned_abs())
+    }
+}
+
+fn unsigned_words(n: u64) -> String {
+    const ONES: [&str; 20] = [
+        "zero",
+        "one",
+        "two",
+        "three",
+        "four",
+        "five",
+        "six",
+        "seven",
+        "eight",
+        "nine",
+        "ten",
+        "eleven",
+        "twelve",
+        "thirteen",
+        "fourteen",
+        "fifteen",
+        "sixteen",
+        "seventeen",
+        "eighteen",
+        "nineteen",
+    ];
+    const TENS: [&str; 10] = [
+        "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+    ];
+    const SCALES: [(u64, &str); 7] = [
+        (1_000_000_000_000_000_000, "quintillion"),
+        (1_000_000_000_000_000, "quadrillion"),
+        (1_000_000_000_000, "trillion"),
+        (1_000_000_000, "billion"),
+        (1_000_000, "million"),
+        (1_000, "thousand"),
+        (100, "hundred"),
+    ];
+
+    match n {
+        0..=19 => ONES[n as usize].to_string(),
+        20..=99 => match n as usize % 10 {
+            0 => TENS[n as usize / 10].to_string(),
+            ones => format!("{}-{}", TENS[n as usize / 10], ONES[ones]),
+        },
+        _ => {
+            let (scale, name) = SCALES
+                .into_iter()
+                .find(|(scale, _)| n >= *scale)
+                .expect("n is at least 100");
+            let head = format!("{} {name}", unsigned_words(n / scale));
+            match n % scale {
+                0 => head,
+                rest => format!("{head} {}", unsigned_words(rest)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(name: &str, args: &[&str]) -> Filter {
+        Filter {
+            name: name.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_truncate_is_grapheme_and_width_aware() {
+        let plain = OutputMarkup::Plain;
+        let truncate = filter("truncate", &["5"]);
+
+        assert_eq!(truncate.apply("Firefox", plain).unwrap(), "Fire…");
+        assert_eq!(truncate.apply("Fire", plain).unwrap(), "Fire");
+        // CJK characters are two columns wide
+        assert_eq!(truncate.apply("日本語のタイトル", plain).unwrap(), "日本…");
+        // The family emoji is a single grapheme, never cut in the middle
+        assert_eq!(
+            filter("truncate", &["3", ""])
+                .apply("👨‍👩‍👧abc", plain)
+                .unwrap(),
+            "👨‍👩‍👧a"
+        );
+    }
+
+    #[test]
+    fn test_filters_keep_pango_markup_valid() {
+        let pango = OutputMarkup::Pango;
+
+        assert_eq!(
+            filter("truncate", &["4"])
+                .apply("Q&amp;A — Slack", pango)
+                .unwrap(),
+            "Q&amp;A…"
+        );
+        assert_eq!(
+            filter("upper", &[])
+                .apply("<span color='red'>q&amp;a</span>", pango)
+                .unwrap(),
+            "<span color='red'>Q&amp;A</span>"
+        );
+        assert_eq!(
+            filter("truncate", &["3", "."])
+                .apply("<b>hello</b> world", pango)
+                .unwrap(),
+            "<b>he.</b>"
+        );
+    }
+
+    #[test]
+    fn test_number_filters() {
+        let plain = OutputMarkup::Plain;
+        let apply = |name: &str, value: &str| filter(name, &[]).apply(value, plain).unwrap();
+
+        assert_eq!(apply("sup", "-12"), "⁻¹²");
+        assert_eq!(apply("sub", "-98"), "₋₉₈");
+        assert_eq!(apply("fullwidth", "10"), "１０");
+        assert_eq!(apply("circled", "0"), "⓪");
+        assert_eq!(apply("circled", "3"), "③");
+        assert_eq!(apply("circled", "21"), "㉑");
+        assert_eq!(apply("circled", "50"), "㊿");
+        assert_eq!(apply("circled", "-2"), "-②");
+        assert_eq!(apply("circled", "51"), "51");
+        assert_eq!(apply("roman", "14"), "XIV");
+        assert_eq!(apply("roman", "1994"), "MCMXCIV");
+        assert_eq!(apply("roman", "0"), "0");
+        assert_eq!(apply("words", "0"), "zero");
+        assert_eq!(apply("words", "-21"), "minus twenty-one");
+        assert_eq!(apply("words", "40"), "forty");
+        assert_eq!(apply("words", "1305"), "one thousand three hundred five");
+        // Not a number
+        assert_eq!(apply("roman", "term"), "term");
+    }
+
+    #[test]
+    fn test_other_filters() {
+        let plain = OutputMarkup::Plain;
+
+        assert_eq!(filter("lower", &[]).apply("Kitty", plain).unwrap(), "kitty");
+        assert_eq!(
+            filter("replace", &[" — Mozilla Firefox", ""])
+                .apply("Rust — Mozilla Firefox", plain)
+                .unwrap(),
+            "Rust"
+        );
+        assert_eq!(filter("pad", &["2"]).apply("3", plain).unwrap(), "03");
+        assert_eq!(filter("pad", &["3"]).apply("-3", plain).unwrap(), "-03");
+        assert_eq!(filter("pad", &["4"]).apply("ab", plain).unwrap(), "  ab");
+        assert_eq!(
+            filter("pad", &["3", "."]).apply("ab", plain).unwrap(),
+            ".ab"
+        );
+        assert_eq!(filter("minus", &["10"]).apply("13", plain).unwrap(), "3");
+        assert_eq!(filter("add", &["-2"]).apply("1", plain).unwrap(), "-1");
+        assert_eq!(filter("minus", &["1"]).apply("ab", plain).unwrap(), "ab");
+        assert!(filter("minus", &["a"]).apply("1", plain).is_err());
+        assert!(filter("minus", &[&i64::MIN.to_string()])
+            .apply("1", plain)
+            .is_err());
+        assert_eq!(filter("sub", &["10"]).apply("13", plain).unwrap(), "3");
+        assert_eq!(filter("sub", &[]).apply("13", plain).unwrap(), "₁₃");
+        assert!(filter("roman", &["1"]).apply("1", plain).is_err());
+        assert!(filter("truncate", &["a"]).apply("ab", plain).is_err());
+        assert!(filter("nope", &[]).apply("ab", plain).is_err());
+    }
+}
@@ -1,19 +1,72 @@
-use crate::renamer::ConfigFile;
+use crate::config::{DedupBy, OutputMarkup};
+use crate::renamer::filters::{superscript, Filter};
+use crate::renamer::ConfigFormatRaw;
 use crate::renamer::IconStatus::*;
-use crate::{AppClient, Renamer};
+use crate::{AppClient, ClientFlags, Renamer};
 use hyprland::data::FullscreenMode;
-use std::collections::HashMap;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
 use strfmt::strfmt;
 
 #[derive(Clone)]
 pub struct AppWorkspace {
     pub id: i32,
+    pub name: String,
+    pub monitor: String,
+    pub monitor_id: i128,
+    pub last_window_title: String,
+    pub is_active: bool,
+    pub is_visible: bool,
     pub clients: Vec<AppClient>,
 }
 
 impl AppWorkspace {
     pub fn new(id: i32, clients: Vec<AppClient>) -> Self {
-        AppWorkspace { id, clients }
+        AppWorkspace {
+            id,
+            name: String::new(),
+            monitor: String::new(),
+            monitor_id: 0,
+            last_window_title: String::new(),
+            is_active: false,
+            is_visible: false,
+            clients,
+        }
+    }
+
+    /// `term` for the special workspace `special:term`, special workspaces having negative ids.
+    pub fn special_name(&self) -> Option<&str> {
+        (self.id < 0).then(|| self.name.strip_prefix("special:").unwrap_or(&self.name))
+    }
+
+    /// Without the hidden and swallowed clients, unless they have their own formatter.
+    pub fn without_hidden_clients(&self, config_format: &ConfigFormatRaw) -> Self {
+        let config_format = workspace_format(config_format, self.id, &self.monitor);
+        AppWorkspace {
+            clients: self
+                .clients
+                .iter()
+                .filter(|client| {
+                    (!client.flags.is_hidden || config_format.client_hidden.is_some())
+                        && (!client.flags.is_swallowed || config_format.client_swallowed.is_some())
+                })
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// `D1` for `DP-1`, `HA1` for `HDMI-A-1`: initials and numbers of the connector name.
+    pub fn monitor_short(&self) -> String {
+        self.monitor
+            .split(['-', '_', ' '])
+            .filter_map(|part| match part.parse::<u32>() {
+                Ok(_) => Some(part.to_string()),
+                Err(_) => part.chars().next().map(|c| c.to_uppercase().to_string()),
+            })
+            .collect()
     }
 }
 
@@ -21,24 +74,39 @@ impl Renamer {
     pub fn generate_workspaces_string(
         &self,
         workspaces: Vec<AppWorkspace>,
-        config: &ConfigFile,
+        config_format: &ConfigFormatRaw,
     ) -> HashMap<i32, String> {
-        let vars = HashMap::from([("delim".to_string(), config.format.delim.to_string())]);
+        let client_formats = self
+            .cfg
+            .lock()
+            .map(|cfg| cfg.config.client_format.clone())
+            .unwrap_or_default();
+
         workspaces
             .iter()
             .map(|workspace| {
-                let mut counted =
-                    generate_counted_clients(workspace.clients.clone(), config.format.dedup);
+                let config_format =
+                    &workspace_format(config_format, workspace.id, &workspace.monitor);
+                let markup = config_format.output_markup;
+                let vars = HashMap::from([(
+                    "delim".to_string(),
+                    markup_template(&config_format.delim, markup),
+                )]);
+                let clients = match config_format.client_grouped {
+                    Some(_) => collapse_groups(workspace.clients.clone()),
+                    None => workspace.clients.clone(),
+                };
+                let mut counted = generate_counted_clients(clients.clone(), config_format);
 
-                if config.format.dedup {
+                if config_format.dedup {
                     let mut ordered = Vec::with_capacity(counted.len());
                     let mut used = vec![false; counted.len()];
 
-                    for client in &workspace.clients {
+                    for client in &clients {
                         if let Some((idx, _)) = counted
                             .iter()
                             .enumerate()
-                            .find(|(idx, (c, _))| !used[*idx] && c == client)
+                            .find(|(idx, (c, _))| !used[*idx] && is_dup(c, client, config_format))
                         {
                             ordered.push(counted[idx].clone());
                             used[idx] = true;
@@ -56,18 +124,41 @@ impl Renamer {
                     counted = ordered;
                 }
 
-                let workspace_output = counted
+                let (mut counted, hidden_count) =
+                    truncate_clients(counted, config_format.max_clients);
+
+                let mut workspace_output = counted
                     .iter_mut()
-                    .map(|(client, counter)| self.handle_new_client(client, *counter, config))
-                    .take(
-                        config
-                            .format
-                            .max_clients
-                            .map_or(usize::MAX, |max| max as usize),
-                    )
+                    .map(|(client, counter)| {
+                        let config_format = client_format(config_format, &client_formats, client);
+                        self.handle_new_client(client, *counter, &config_format)
+                            .unwrap_or_else(|e| {
+                                eprintln!("Unable to format client {}: {e}", client.class);
+                                String::new()
+                            })
+                    })
                     .collect::<Vec<String>>();
 
-                let delimiter = formatter("{delim}", &vars);
+                if let Some(overflow) = config_format
+                    .client_overflow
+                    .as_ref()
+                    .filter(|_| hidden_count > 0)
+                {
+                    let vars = HashMap::from([
+                        (
+                            "delim".to_string(),
+                            markup_template(&config_format.delim, markup),
+                        ),
+                        ("hidden_count".to_string(), hidden_count.to_string()),
+                        ("hidden_count_sup".to_string(), to_superscript(hidden_count)),
+                    ]);
+                    match formatter(&markup_template(overflow, markup), &vars, markup) {
+                        Ok(overflow) => workspace_output.push(overflow),
+                        Err(e) => eprintln!("Unable to format client_overflow: {e}"),
+                    }
+                }
+
+                let delimiter = formatter("{delim}", &vars, markup).unwrap_or_default();
                 let joined_string = workspace_output.join(&delimiter);
 
                 (workspace.id, joined_string)
@@ -75,27 +166,90 @@ impl Renamer {
             .collect()
     }
 
-    fn handle_new_client(&self, client: &AppClient, counter: i32, config: &ConfigFile) -> String {
-        let config_format = &config.format;
+    fn handle_new_client(
+        &self,
+        client: &AppClient,
+        counter: i32,
+        config_format: &ConfigFormatRaw,
+    ) -> Result<String, FormatError> {
         let client = client.clone();
+        let markup = config_format.output_markup;
 
         let is_dedup = config_format.dedup && (counter > 1);
-        let is_dedup_inactive_fullscreen = config_format.dedup_inactive_fullscreen;
+        let fullscreen_mode = shown_fullscreen_mode(&client, config_format);
 
         let counter_sup = to_superscript(counter);
+        let group_count = client
+            .flags
+            .group
+            .as_ref()
+            .map_or(1, |(_, size)| *size as i32);
         let prev_counter = (counter - 1).to_string();
         let prev_counter_sup = to_superscript(counter - 1);
-        let delim = &config_format.delim.to_string();
+        let delim = &markup_template(&config_format.delim, markup);
 
-        let fmt_client = &config_format.client.to_string();
-        let fmt_client_active = &config_format.client_active.to_string();
-        let fmt_client_fullscreen = &config_format.client_fullscreen.to_string();
-        let fmt_client_dup = &config_format.client_dup.to_string();
-        let fmt_client_dup_fullscreen = &config_format.client_dup_fullscreen.to_string();
+        let fmt_client = &markup_template(&config_format.client, markup);
+        let fmt_client_active = &markup_template(&config_format.client_active, markup);
+        let fmt_client_fullscreen = &markup_template(&config_format.client_fullscreen, markup);
+        let fmt_client_dup = &markup_template(&config_format.client_dup, markup);
+        let fmt_client_dup_fullscreen =
+            &markup_template(&config_format.client_dup_fullscreen, markup);
+        let fmt_client_maximized = &config_format
+            .client_maximized
+            .as_ref()
+            .map_or(fmt_client_fullscreen.clone(), |fmt| {
+                markup_template(fmt, markup)
+            });
+        let fmt_client_dup_maximized = &config_format
+            .client_dup_maximized
+            .as_ref()
+            .map_or(fmt_client_dup_fullscreen.clone(), |fmt| {
+                markup_template(fmt, markup)
+            });
 
         let mut vars = HashMap::from([
-            ("title".to_string(), client.title.clone()),
-            ("class".to_string(), client.class.clone()),
+            (
+                "title".to_string(),
+                literal(&markup_value(&client.title, markup)),
+            ),
+            (
+                "class".to_string(),
+                literal(&markup_value(&client.class, markup)),
+            ),
+            ("title_raw".to_string(), literal(&client.title)),
+            ("class_raw".to_string(), literal(&client.class)),
+            ("active".to_string(), client.is_active.to_string()),
+            ("urgent".to_string(), client.flags.is_urgent.to_string()),
+            ("hidden".to_string(), client.flags.is_hidden.to_string()),
+            (
+                "swallowed".to_string(),
+                client.flags.is_swallowed.to_string(),
+            ),
+            (
+                "is_last_focused".to_string(),
+                client.flags.is_last_focused.to_string(),
+            ),
+            ("floating".to_string(), client.flags.is_floating.to_string()),
+            ("pinned".to_string(), client.flags.is_pinned.to_string()),
+            ("xwayland".to_string(), client.flags.is_xwayland.to_string()),
+            (
+                "grouped".to_string(),
+                client.flags.group.is_some().to_string(),
+            ),
+            ("group_count".to_string(), group_count.to_string()),
+            ("group_count_sup".to_string(), to_superscript(group_count)),
+            (
+                "fullscreen".to_string(),
+                matches!(
+                    client.is_fullscreen,
+                    FullscreenMode::Fullscreen | FullscreenMode::MaximizedFullscreen
+                )
+                .to_string(),
+            ),
+            (
+                "maximized".to_string(),
+                (client.is_fullscreen == FullscreenMode::Maximized).to_string(),
+            ),
             ("counter".to_string(), counter.to_string()),
             ("counter_unfocused".to_string(), prev_counter),
             ("counter_sup".to_string(), counter_sup),
@@ -105,18 +259,22 @@ impl Renamer {
 
         // get regex captures and merge them with vars
         if let Some(re_captures) = client.matched_rule.captures() {
+            let re_captures = re_captures
+                .into_iter()
+                .map(|(k, v)| (k, literal(&markup_value(&v, markup))))
+                .collect();
             merge_vars(&mut vars, re_captures);
         };
 
         let icon = match (client.is_active, client.matched_rule.clone()) {
+            // The inactive icon goes through client_active, its `{icon}` with any filter
             (true, c @ Inactive(_)) => {
-                vars.insert("default_icon".to_string(), c.icon());
-                formatter(
-                    &fmt_client_active.replace("{icon}", "{default_icon}"),
-                    &vars,
-                )
+                let inactive_icon = markup_template(&c.icon(), markup);
+                vars.insert("default_icon".to_string(), inactive_icon.clone());
+                vars.insert("icon".to_string(), inactive_icon);
+                literal(&formatter(fmt_client_active, &vars, markup)?)
             }
-            (_, c) => c.icon(),
+            (_, c) => markup_template(&c.icon(), markup),
         };
 
         vars.insert("icon".to_string(), icon);
@@ -131,55 +289,670 @@ impl Renamer {
             println!("client: {client:#?}\nformatter vars => {vars:#?}");
         }
 
-        let is_grouped = client.is_fullscreen != FullscreenMode::None
-            && (client.is_active || !is_dedup_inactive_fullscreen);
+        let fmt_client_shown = match (fullscreen_mode, is_dedup) {
+            (FullscreenMode::None, true) => fmt_client_dup,
+            (FullscreenMode::None, false) => fmt_client,
+            (FullscreenMode::Maximized, true) => fmt_client_dup_maximized,
+            (FullscreenMode::Maximized, false) => fmt_client_maximized,
+            (_, true) => fmt_client_dup_fullscreen,
+            (_, false) => fmt_client_fullscreen,
+        };
+        let shown = formatter(fmt_client_shown, &vars, markup)?;
+
+        // The first set formatter of the client states wraps the usual one, as {client}
+        let state_formatters = [
+            (client.flags.is_swallowed, &config_format.client_swallowed),
+            (client.flags.is_hidden, &config_format.client_hidden),
+            (client.flags.is_urgent, &config_format.client_urgent),
+            (
+                client.flags.is_last_focused,
+                &config_format.client_last_focused,
+            ),
+            (client.flags.group.is_some(), &config_format.client_grouped),
+            (client.flags.is_pinned, &config_format.client_pinned),
+            (client.flags.is_floating, &config_format.client_floating),
+            (client.flags.is_xwayland, &config_format.client_xwayland),
+        ];
+        match state_formatters
+            .into_iter()
+            .find_map(|(is_state, fmt)| fmt.as_ref().filter(|_| is_state))
+        {
+            Some(fmt_client_state) => {
+                vars.insert("client".to_string(), literal(&shown));
+                formatter(&markup_template(fmt_client_state, markup), &vars, markup)
+            }
+            None => Ok(shown),
+        }
+    }
+}
+
+/// The format of a workspace, with the overrides of its monitor and its own.
+pub fn workspace_format<'a>(
+    config_format: &'a ConfigFormatRaw,
+    id: i32,
+    monitor: &str,
+) -> Cow<'a, ConfigFormatRaw> {
+    config_format
+        .for_workspace(id, monitor)
+        .unwrap_or_else(|e| {
+            eprintln!("Unable to apply the format of workspace {id}: {e}");
+            Cow::Borrowed(config_format)
+        })
+}
+
+/// The format of a client, with the overrides of the first matching `[client_format]`.
+fn client_format<'a>(
+    config_format: &'a ConfigFormatRaw,
+    client_formats: &[(Regex, toml::Table)],
+    client: &AppClient,
+) -> Cow<'a, ConfigFormatRaw> {
+    let overrides = client_formats
+        .iter()
+        .find(|(rule, _)| rule.is_match(&client.class));
+
+    match overrides.map(|(_, overrides)| config_format.with_overrides(overrides)) {
+        Some(Ok(format)) => Cow::Owned(format),
+        Some(Err(e)) => {
+            eprintln!("Unable to apply client_format of {}: {e}", client.class);
+            Cow::Borrowed(config_format)
+        }
+        None => Cow::Borrowed(config_format),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    Parse(String),
+    Recursive(String),
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FormatError::Parse(e) => write!(f, "{e}"),
+            FormatError::Recursive(name) => write!(f, "{{{name}}} refers to itself"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Placeholder(Placeholder),
+    Conditional(Condition, Vec<Node>, Vec<Node>),
+}
+
+/// A `{name|filter(args)|filter:spec}` placeholder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Placeholder {
+    raw: String,
+    name: String,
+    filters: Vec<Filter>,
+    spec: Option<String>,
+}
+
+/// The condition of a `{?cond}...{:}...{/}` section, `||` of `&&` of tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Condition(Vec<Vec<Test>>);
+
+/// `var`, `!var` or `var > value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Test {
+    negated: bool,
+    name: String,
+    comparison: Option<(Operator, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+const OPERATORS: [(&str, Operator); 6] = [
+    ("==", Operator::Eq),
+    ("!=", Operator::Ne),
+    (">=", Operator::Ge),
+    ("<=", Operator::Le),
+    (">", Operator::Gt),
+    ("<", Operator::Lt),
+];
+
+/// Renders a template. Vars values are templates too (`{icon}` can hold `{class}`),
+/// so values coming from the clients must go through `literal`.
+pub fn formatter(
+    fmt: &str,
+    vars: &HashMap<String, String>,
+    markup: OutputMarkup,
+) -> Result<String, FormatError> {
+    render(fmt, vars, markup, &mut vec![])
+}
+
+/// Checks the syntax of a template.
+pub fn validate_template(fmt: &str) -> Result<(), FormatError> {
+    parse_template(fmt).map(|_| ())
+}
+
+/// The names of the placeholders and conditions of a template, none if it is invalid.
+pub fn template_vars(fmt: &str) -> HashSet<String> {
+    fn collect(nodes: &[Node], names: &mut HashSet<String>) {
+        for node in nodes {
+            match node {
+                Node::Text(_) => {}
+                Node::Placeholder(placeholder) => {
+                    names.insert(placeholder.name.clone());
+                }
+                Node::Conditional(condition, then, otherwise) => {
+                    names.extend(condition.0.iter().flatten().map(|test| test.name.clone()));
+                    collect(then, names);
+                    collect(otherwise, names);
+                }
+            }
+        }
+    }
+
+    let mut names = HashSet::new();
+    if let Ok(nodes) = parse_template(fmt) {
+        collect(&nodes, &mut names);
+    }
+    names
+}
+
+/// Checks the formatters of a config: syntax, and placeholders referring to themselves.
+pub fn validate_format(format: &ConfigFormatRaw) -> Result<(), String> {
+    let formatters = [
+        ("delim", &format.delim),
+        ("workspace", &format.workspace),
+        ("workspace_empty", &format.workspace_empty),
+        ("client", &format.client),
+        ("client_active", &format.client_active),
+        ("client_fullscreen", &format.client_fullscreen),
+        ("client_dup", &format.client_dup),
+        ("client_dup_active", &format.client_dup_active),
+        ("client_dup_fullscreen", &format.client_dup_fullscreen),
+    ];
+
+    // The formatters usable as placeholders in the client formatters
+    let vars = HashMap::from([
+        ("client".to_string(), format.client.clone()),
+        ("client_dup".to_string(), format.client_dup.clone()),
+        (
+            "client_fullscreen".to_string(),
+            format.client_fullscreen.clone(),
+        ),
+    ]);
+
+    let optional_formatters = [
+        ("workspace_active", &format.workspace_active),
+        ("workspace_visible", &format.workspace_visible),
+        ("workspace_special", &format.workspace_special),
+        ("client_overflow", &format.client_overflow),
+        ("client_maximized", &format.client_maximized),
+        ("client_dup_maximized", &format.client_dup_maximized),
+        ("client_urgent", &format.client_urgent),
+        ("client_hidden", &format.client_hidden),
+        ("client_swallowed", &format.client_swallowed),
+        ("client_last_focused", &format.client_last_focused),
+        ("client_grouped", &format.client_grouped),
+        ("client_pinned", &format.client_pinned),
+        ("client_floating", &format.client_floating),
+        ("client_xwayland", &format.client_xwayland),
+        ("workspace_urgent", &format.workspace_urgent),
+    ];
+    let optional_formatters = optional_formatters
+        .iter()
+        .filter_map(|(name, fmt)| fmt.as_ref().map(|fmt| (*name, fmt)));
+
+    formatters
+        .into_iter()
+        .chain(optional_formatters)
+        .try_for_each(|(name, fmt)| {
+            formatter(fmt, &vars, OutputMarkup::Plain)
+                .map(|_| ())
+                .map_err(|e| format!("format.{name}: {e}"))
+        })
+}
+
+/// Escapes the braces of a value, so it is never read as a template.
+pub fn literal(value: &str) -> String {
+    value.replace('{', "{{").replace('}', "}}")
+}
+
+fn render(
+    fmt: &str,
+    vars: &HashMap<String, String>,
+    markup: OutputMarkup,
+    stack: &mut Vec<String>,
+) -> Result<String, FormatError> {
+    render_nodes(&parse_template(fmt)?, vars, markup, stack)
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    vars: &HashMap<String, String>,
+    markup: OutputMarkup,
+    stack: &mut Vec<String>,
+) -> Result<String, FormatError> {
+    let mut output = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Placeholder(placeholder) => {
+                match render_var(&placeholder.name, vars, markup, stack)? {
+                    Some(value) => output.push_str(&apply_placeholder(placeholder, value, markup)?),
+                    // Unknown placeholders, like a missing `{match2}`, are kept as is
+                    None => output.push_str(&placeholder.raw),
+                }
+            }
+            Node::Conditional(condition, then, otherwise) => {
+                let branch = match eval_condition(condition, vars, markup, stack)? {
+                    true => then,
+                    false => otherwise,
+                };
+                output.push_str(&render_nodes(branch, vars, markup, stack)?);
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn render_var(
+    name: &str,
+    vars: &HashMap<String, String>,
+    markup: OutputMarkup,
+    stack: &mut Vec<String>,
+) -> Result<Option<String>, FormatError> {
+    let Some(value) = vars.get(name) else {
+        return Ok(None);
+    };
+
+    if stack.iter().any(|n| n == name) {
+        return Err(FormatError::Recursive(name.to_string()));
+    }
+
+    stack.push(name.to_string());
+    let value = render(value, vars, markup, stack);
+    stack.pop();
+
+    value.map(Some)
+}
+
+fn eval_condition(
+    condition: &Condition,
+    vars: &HashMap<String, String>,
+    markup: OutputMarkup,
+    stack: &mut Vec<String>,
+) -> Result<bool, FormatError> {
+    for tests in &condition.0 {
+        let mut all = true;
+        for test in tests {
+            let value = render_var(&test.name, vars, markup, stack)?.unwrap_or_default();
+            let value = strip_markup(&value);
+            let result = match &test.comparison {
+                None => !matches!(value.trim(), "" | "0" | "false"),
+                Some((operator, other)) => compare(value.trim(), *operator, other),
+            };
+            if result == test.negated {
+                all = false;
+                break;
+            }
+        }
+        if all {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Compares as numbers when both sides are numbers, as strings otherwise.
+fn compare(value: &str, operator: Operator, other: &str) -> bool {
+    let ordering = match (value.parse::<f64>(), other.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b),
+        _ => Some(value.cmp(other)),
+    };
+
+    let Some(ordering) = ordering else {
+        return false;
+    };
+
+    match operator {
+        Operator::Eq => ordering.is_eq(),
+        Operator::Ne => ordering.is_ne(),
+        Operator::Ge => ordering.is_ge(),
+        Operator::Le => ordering.is_le(),
+        Operator::Gt => ordering.is_gt(),
+        Operator::Lt => ordering.is_lt(),
+    }
+}
+
+fn apply_placeholder(
+    placeholder: &Placeholder,
+    value: String,
+    markup: OutputMarkup,
+) -> Result<String, FormatError> {
+    let value = placeholder
+        .filters
+        .iter()
+        .try_fold(value, |value, filter| filter.apply(&value, markup))
+        .map_err(|e| FormatError::Parse(format!("{}: {e}", placeholder.raw)))?;
+
+    Ok(match &placeholder.spec {
+        Some(spec) => {
+            let vars = HashMap::from([("value".to_string(), value.clone())]);
+            strfmt(&format!("{{value:{spec}}}"), &vars).unwrap_or(value)
+        }
+        None => value,
+    })
+}
+
+/// A section being parsed: the root, or a `{?cond}` waiting for its `{/}`.
+struct Section {
+    condition: Option<Condition>,
+    then: Vec<Node>,
+    otherwise: Option<Vec<Node>>,
+}
+
+impl Section {
+    fn nodes(&mut self) -> &mut Vec<Node> {
+        self.otherwise.as_mut().unwrap_or(&mut self.then)
+    }
+}
+
+/// Parses a template, `{{` and `}}` being literal braces.
+fn parse_template(fmt: &str) -> Result<Vec<Node>, FormatError> {
+    let error = |e: &str| Err(FormatError::Parse(format!("{e} in '{fmt}'")));
+    let mut sections = vec![Section {
+        condition: None,
+        then: vec![],
+        otherwise: None,
+    }];
+    let mut text = String::new();
+    let mut rest = fmt;
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push(c);
+            rest = &rest[2..];
+            continue;
+        } else if c == '}' {
+            return error("unmatched '}', use '}}' for a literal brace");
+        } else if c != '{' {
+            text.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let Some(end) = placeholder_end(rest) else {
+            return error("unclosed '{', use '{{' for a literal brace");
+        };
+        let tag = &rest[..end];
+        rest = &rest[end..];
+
+        let section = sections
+            .last_mut()
+            .expect("the root section is never closed");
+        section.nodes().push(Node::Text(std::mem::take(&mut text)));
 
-        match (is_grouped, is_dedup) {
-            (true, true) => formatter(fmt_client_dup_fullscreen, &vars),
-            (false, true) => formatter(fmt_client_dup, &vars),
-            (true, false) => formatter(fmt_client_fullscreen, &vars),
-            (false, false) => formatter(fmt_client, &vars),
+        match tag {
+            "{:}" if section.condition.is_some() && section.otherwise.is_none() => {
+                section.otherwise = Some(vec![]);
+            }
+            "{:}" => return error("'{:}' outside of a '{?condition}'"),
+            "{/}" if section.condition.is_some() => {
+                let section = sections.pop().expect("a conditional section is open");
+                let node = Node::Conditional(
+                    section.condition.expect("checked above"),
+                    section.then,
+                    section.otherwise.unwrap_or_default(),
+                );
+                sections
+                    .last_mut()
+                    .expect("the root section is never closed")
+                    .nodes()
+                    .push(node);
+            }
+            "{/}" => return error("'{/}' without '{?condition}'"),
+            _ if tag.starts_with("{?") => {
+                let Some(condition) = parse_condition(&tag[2..tag.len() - 1]) else {
+                    return error(&format!("invalid condition {tag}"));
+                };
+                sections.push(Section {
+                    condition: Some(condition),
+                    then: vec![],
+                    otherwise: None,
+                });
+            }
+            _ => match parse_placeholder(tag) {
+                Some(placeholder) => {
+                    for filter in &placeholder.filters {
+                        // Unknown filters and bad arguments fail on any value
+                        if let Err(e) = filter.apply("", OutputMarkup::Plain) {
+                            return error(&e);
+                        }
+                    }
+                    section.nodes().push(Node::Placeholder(placeholder));
+                }
+                None => return error(&format!("invalid placeholder {tag}")),
+            },
         }
     }
+
+    if sections.len() > 1 {
+        return error("missing '{/}'");
+    }
+
+    let mut root = sections.pop().expect("the root section is never closed");
+    root.then.push(Node::Text(text));
+    Ok(root.then)
+}
+
+fn parse_condition(condition: &str) -> Option<Condition> {
+    split_outside_quotes(condition, "||")
+        .into_iter()
+        .map(|tests| {
+            split_outside_quotes(tests, "&&")
+                .into_iter()
+                .map(parse_test)
+                .collect()
+        })
+        .collect::<Option<_>>()
+        .map(Condition)
+}
+
+fn parse_test(test: &str) -> Option<Test> {
+    let test = test.trim();
+    let (negated, test) = match test.strip_prefix('!') {
+        Some(test) if !test.starts_with('=') => (true, test.trim()),
+        _ => (false, test),
+    };
+
+    let comparison = OPERATORS.iter().find_map(|(symbol, operator)| {
+        test.split_once(symbol)
+            .map(|(name, value)| (name.trim(), *operator, value.trim()))
+    });
+
+    let (name, comparison) = match comparison {
+        // An empty value must be quoted: `{?title == ""}`
+        Some((_, _, "")) => return None,
+        Some((name, operator, value)) => (name, Some((operator, unquote(value)))),
+        None => (test, None),
+    };
+
+    is_var_name(name).then(|| Test {
+        negated,
+        name: name.to_string(),
+        comparison,
+    })
+}
+
+fn is_var_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => value.to_string(),
+    }
 }
 
-pub fn formatter(fmt: &str, vars: &HashMap<String, String>) -> String {
-    let mut result = fmt.to_owned();
-    let mut i = 0;
-    loop {
-        if !(result.contains('{') && result.contains('}')) {
-            break result;
+/// Returns the index after the `}` closing the placeholder starting `fmt`.
+fn placeholder_end(fmt: &str) -> Option<usize> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in fmt.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '{' if !in_quotes => return None,
+            '}' if !in_quotes => return Some(i + 1),
+            _ => {}
         }
-        let formatted = strfmt(&result, vars).unwrap_or_else(|_| result.clone());
-        if formatted == result {
-            break result;
+    }
+    None
+}
+
+fn parse_placeholder(raw: &str) -> Option<Placeholder> {
+    let body = &raw[1..raw.len() - 1];
+    let mut parts = split_outside_quotes(body, "|").into_iter();
+    let mut name = parts.next()?.trim().to_string();
+    let mut filters: Vec<String> = parts.map(|part| part.trim().to_string()).collect();
+
+    // The format spec is at the end: `{id:>3}` or `{id|pad(2):>3}`
+    let last = filters.last_mut().unwrap_or(&mut name);
+    let spec = match split_outside_quotes(last, ":").as_slice() {
+        [value, spec] => {
+            let spec = spec.to_string();
+            *last = value.trim().to_string();
+            Some(spec)
         }
-        result = formatted;
-        i += 1;
-        if i > 3 {
-            eprintln!("placeholders loop, aborting");
-            break result;
+        _ => None,
+    };
+
+    if !is_var_name(&name) {
+        return None;
+    }
+
+    Some(Placeholder {
+        raw: raw.to_string(),
+        name,
+        filters: filters
+            .iter()
+            .map(|filter| parse_filter(filter))
+            .collect::<Option<_>>()?,
+        spec,
+    })
+}
+
+/// Parses `name` or `name(arg, "quoted arg")`.
+fn parse_filter(filter: &str) -> Option<Filter> {
+    let (name, args) = match filter.split_once('(') {
+        Some((name, args)) => (name.trim(), args.strip_suffix(')')?),
+        None => (filter, ""),
+    };
+
+    let args = split_outside_quotes(args, ",")
+        .into_iter()
+        .map(str::trim)
+        .filter(|arg| !arg.is_empty())
+        .map(unquote)
+        .collect();
+
+    Some(Filter {
+        name: name.to_string(),
+        args,
+    })
+}
+
+fn split_outside_quotes<'a>(value: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = vec![];
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            _ if !in_quotes && i >= start && value[i..].starts_with(separator) => {
+                parts.push(&value[start..i]);
+                start = i + separator.len();
+            }
+            _ => {}
         }
     }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Escapes a value given by a client (title, class...) so it can't break the markup.
+pub fn markup_value(value: &str, markup: OutputMarkup) -> String {
+    match markup {
+        OutputMarkup::Pango => escape_markup(value),
+        OutputMarkup::Plain => value.to_string(),
+    }
+}
+
+/// Removes the markup of a template from the config when the output is plain.
+pub fn markup_template(template: &str, markup: OutputMarkup) -> String {
+    match markup {
+        OutputMarkup::Pango => template.to_string(),
+        OutputMarkup::Plain => strip_markup(template),
+    }
+}
+
+pub fn escape_markup(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
+
+pub fn strip_markup(template: &str) -> String {
+    static TAGS: OnceLock<Regex> = OnceLock::new();
+    let tags = TAGS.get_or_init(|| Regex::new(r"</?[A-Za-z][^>]*>").unwrap());
+
+    unescape_markup(&tags.replace_all(template, ""))
+}
+
+pub fn unescape_markup(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&apos;", "'")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
 }
 
 pub fn generate_counted_clients(
     clients: Vec<AppClient>,
-    need_dedup: bool,
+    config_format: &ConfigFormatRaw,
 ) -> Vec<(AppClient, i32)> {
-    if need_dedup {
+    if config_format.dedup {
         let mut sorted_clients = clients;
         sorted_clients.sort_by(|a, b| {
             let bf = b.is_fullscreen != FullscreenMode::None;
             let af = a.is_fullscreen != FullscreenMode::None;
             bf.cmp(&af)
         });
-        sorted_clients.sort_by(|a, b| b.is_active.cmp(&a.is_active));
+        sorted_clients.sort_by_key(|c| std::cmp::Reverse(c.is_active));
 
         sorted_clients
             .into_iter()
             .fold(vec![], |mut state, client| {
-                match state.iter_mut().find(|(c, _)| c == &client) {
+                match state
+                    .iter_mut()
+                    .find(|(c, _)| is_dup(c, &client, config_format))
+                {
                     Some(c) => c.1 += 1,
                     None => state.push((client, 1)),
                 }
@@ -190,27 +963,98 @@ pub fn generate_counted_clients(
     }
 }
 
+/// Keeps one client by tabbed group, the active one if any.
+fn collapse_groups(clients: Vec<AppClient>) -> Vec<AppClient> {
+    let mut collapsed: Vec<AppClient> = Vec::with_capacity(clients.len());
+    for client in clients {
+        let group = client.flags.group.clone().map(|(first, _)| first);
+        let same_group = group.and_then(|group| {
+            collapsed.iter_mut().find(|c| {
+                c.flags
+                    .group
+                    .as_ref()
+                    .is_some_and(|(first, _)| *first == group)
+            })
+        });
+
+        match same_group {
+            Some(shown) if client.is_active => *shown = client,
+            Some(_) => {}
+            None => collapsed.push(client),
+        }
+    }
+    collapsed
+}
+
+/// The fullscreen mode of the client, `None` for an inactive client with `dedup_inactive_*`.
+fn shown_fullscreen_mode(client: &AppClient, config_format: &ConfigFormatRaw) -> FullscreenMode {
+    if !client.is_active && config_format.dedup_inactive(client.is_fullscreen) {
+        FullscreenMode::None
+    } else {
+        client.is_fullscreen
+    }
+}
+
+/// Whether two clients are counted as one by `dedup`, grouped by `dedup_by`,
+/// and with `dedup_same_state` only if they are both active, urgent, last focused, or fullscreen.
+/// A state without its own formatter is not shown, so it does not split the clients.
+fn is_dup(a: &AppClient, b: &AppClient, config_format: &ConfigFormatRaw) -> bool {
+    let same_flag = |fmt: &Option<String>, flag: fn(&ClientFlags) -> bool| {
+        fmt.is_none() || flag(&a.flags) == flag(&b.flags)
+    };
+    let same_state = a.is_active == b.is_active
+        && same_flag(&config_format.client_urgent, |f| f.is_urgent)
+        && same_flag(&config_format.client_grouped, |f| f.group.is_some())
+        && same_flag(&config_format.client_hidden, |f| f.is_hidden)
+        && same_flag(&config_format.client_swallowed, |f| f.is_swallowed)
+        && same_flag(&config_format.client_last_focused, |f| f.is_last_focused)
+        && shown_fullscreen_mode(a, config_format) == shown_fullscreen_mode(b, config_format);
+    if config_format.dedup_same_state && !same_state {
+        return false;
+    }
+
+    match config_format.dedup_by {
+        DedupBy::Rule if a.is_active == b.is_active => a.matched_rule == b.matched_rule,
+        // The active and inactive icons differ, the rules may not
+        DedupBy::Rule => a.matched_rule.rule() == b.matched_rule.rule(),
+        DedupBy::Icon => a.matched_rule.icon() == b.matched_rule.icon(),
+        DedupBy::Class => a.class == b.class,
+        DedupBy::InitialClass => a.initial_class == b.initial_class,
+    }
+}
+
+/// Keeps the first `max_clients` clients, the active one always being shown,
+/// and returns how many clients were hidden.
+fn truncate_clients(
+    mut counted: Vec<(AppClient, i32)>,
+    max_clients: Option<i32>,
+) -> (Vec<(AppClient, i32)>, i32) {
+    let max = max_clients.map_or(usize::MAX, |max| max.max(0) as usize);
+    if counted.len() <= max {
+        return (counted, 0);
+    }
+
+    let active = counted.iter().position(|(client, _)| client.is_active);
+    let mut hidden = counted.split_off(max);
+    if let Some(active) = active.filter(|&active| active >= max) {
+        let active = hidden.remove(active - max);
+        // In place of the last shown one, or alone with `max_clients = 0`
+        if let Some(last) = counted.pop() {
+            hidden.insert(0, last);
+        }
+        counted.push(active);
+    }
+
+    let hidden_count = hidden.iter().map(|(_, counter)| counter).sum();
+    (counted, hidden_count)
+}
+
 fn merge_vars(map1: &mut HashMap<String, String>, map2: HashMap<String, String>) {
     map1.extend(map2);
 }
 
 pub fn to_superscript(number: i32) -> String {
-    let m: HashMap<_, _> = [
-        ('0', "⁰"),
-        ('1', "¹"),
-        ('2', "²"),
-        ('3', "³"),
-        ('4', "⁴"),
-        ('5', "⁵"),
-        ('6', "⁶"),
-        ('7', "⁷"),
-        ('8', "⁸"),
-        ('9', "⁹"),
-    ]
-    .into_iter()
-    .collect();
-
-    number.to_string().chars().map(|c| m[&c]).collect()
+    superscript(number.into())
 }
 
 #[cfg(test)]
@@ -218,6 +1062,140 @@ mod tests {
     use super::*;
     use crate::renamer::IconConfig::*;
 
+    #[test]
+    fn test_template_vars() {
+        let names = |fmt| {
+            let mut names: Vec<String> = template_vars(fmt).into_iter().collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names("{match10|upper} {?pkg && counter>1}{app}{:}{{match1}}{/}"),
+            ["app", "counter", "match10", "pkg"]
+        );
+        assert!(names("{unclosed").is_empty());
+    }
+
+    #[test]
+    fn test_markup_escape_and_strip() {
+        assert_eq!(escape_markup("Q&A <b>"), "Q&amp;A &lt;b&gt;");
+        assert_eq!(
+            strip_markup("<span color='red'>{icon}</span> &amp; a < b"),
+            "{icon} & a < b"
+        );
+        assert_eq!(markup_value("Q&A", OutputMarkup::Plain), "Q&A");
+        assert_eq!(
+            markup_template("<b>{icon}</b>", OutputMarkup::Pango),
+            "<b>{icon}</b>"
+        );
+    }
+
+    #[test]
+    fn test_conditional_sections() {
+        let vars = HashMap::from([
+            ("counter".to_string(), "2".to_string()),
+            ("counter_sup".to_string(), "²".to_string()),
+            ("active".to_string(), "false".to_string()),
+            ("class".to_string(), "kitty".to_string()),
+            ("icon".to_string(), "{class}".to_string()),
+        ]);
+        let render = |fmt: &str| formatter(fmt, &vars, OutputMarkup::Plain).unwrap();
+
+        assert_eq!(render("{icon}{?counter>1}{counter_sup}{/}"), "kitty²");
+        assert_eq!(render("{icon}{?counter>=3}{counter_sup}{/}"), "kitty");
+        assert_eq!(render("{?active}*{icon}*{:}{icon}{/}"), "kitty");
+        assert_eq!(render("{?!active}{icon}{/}"), "kitty");
+        assert_eq!(render("{?class == \"kitty\" && counter<3}ok{/}"), "ok");
+        assert_eq!(
+            render("{?active || missing}a{:}{?counter!=2}b{:}c{/}{/}"),
+            "c"
+        );
+        assert_eq!(render("{{literal}} {{{icon}}}"), "{literal} {kitty}");
+    }
+
+    #[test]
+    fn test_formatter_errors() {
+        let vars = HashMap::from([
+            ("client".to_string(), "{client_dup}".to_string()),
+            ("client_dup".to_string(), "{client}".to_string()),
+            ("title".to_string(), literal("a {b} }")),
+        ]);
+        let render = |fmt: &str| formatter(fmt, &vars, OutputMarkup::Plain);
+
+        // Client values are never read as templates
+        assert_eq!(render("{title}").unwrap(), "a {b} }");
+        // Unknown placeholders are kept
+        assert_eq!(render("{match2}").unwrap(), "{match2}");
+        assert_eq!(
+            render("{client}"),
+            Err(FormatError::Recursive("client".to_string()))
+        );
+        for invalid in [
+            "a } b",
+            "{title",
+            "{?title}a",
+            "a{/}",
+            "{:}",
+            "{?a >}x{/}",
+            "{title|nope}",
+            "{title|truncate(a)}",
+        ] {
+            assert!(validate_template(invalid).is_err(), "{invalid}");
+        }
+
+        let mut format = crate::config::read_config_file(None, false, false)
+            .unwrap()
+            .format;
+        assert!(validate_format(&format).is_ok());
+        format.client_dup = "{client_fullscreen}".to_string();
+        format.client_fullscreen = "[{client_dup}]".to_string();
+        assert!(validate_format(&format).is_err());
+    }
+
+    #[test]
+    fn test_is_dup() {
+        let client = |class: &str, rule: &str, is_active: bool, is_fullscreen| AppClient {
+            initial_class: class.to_string(),
+            class: class.to_string(),
+            title: "~".to_string(),
+            initial_title: "zsh".to_string(),
+            is_active,
+            is_fullscreen,
+            matched_rule: match is_active {
+                true => Active(Class(rule.to_string(), "term".to_string(), None)),
+                false => Inactive(Class(rule.to_string(), "term".to_string(), None)),
+            },
+            flags: ClientFlags::default(),
+        };
+        let kitty = client(
+            "kitty",
+            "(kitty|alacritty)",
+            false,
+            FullscreenMode::Fullscreen,
+        );
+        let alacritty = client(
+            "alacritty",
+            "(kitty|alacritty)",
+            false,
+            FullscreenMode::Fullscreen,
+        );
+        let active = client("kitty", "(kitty|alacritty)", true, FullscreenMode::None);
+        let not_fullscreen = client("kitty", "(kitty|alacritty)", false, FullscreenMode::None);
+        let other_rule = client("alacritty", "alacritty", false, FullscreenMode::Fullscreen);
+
+        let mut format = ConfigFormatRaw::default();
+        assert!(is_dup(&kitty, &alacritty, &format));
+        assert!(!is_dup(&kitty, &active, &format));
+        assert!(!is_dup(&kitty, &not_fullscreen, &format));
+        assert!(!is_dup(&alacritty, &other_rule, &format));
+
+        format.dedup_inactive_fullscreen = true;
+        assert!(is_dup(&kitty, &not_fullscreen, &format));
+        format.dedup_by = DedupBy::Class;
+        assert!(!is_dup(&kitty, &alacritty, &format));
+        assert!(is_dup(&alacritty, &other_rule, &format));
+    }
+
     #[test]
     fn test_app_workspace_new() {
         let client = AppClient {
@@ -228,7 +1206,7 @@ mod tests {
             is_active: false,
             is_fullscreen: FullscreenMode::Fullscreen,
             matched_rule: Inactive(Default(String::from("DefaultIcon"))),
-            is_dedup_inactive_fullscreen: false,
+            flags: ClientFlags::default(),
         };
 
         let workspace = AppWorkspace::new(1, vec![client]);
@@ -237,7 +1215,7 @@ mod tests {
         assert_eq!(workspace.clients.len(), 1);
         assert_eq!(workspace.clients[0].class, "Class");
         assert_eq!(workspace.clients[0].title, "Title");
-        assert_eq!(workspace.clients[0].is_active, false);
+        assert!(!workspace.clients[0].is_active);
         assert_eq!(
             workspace.clients[0].is_fullscreen,
             FullscreenMode::Fullscreen
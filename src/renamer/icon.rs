@@ -1,3 +1,4 @@
+use crate::renamer::formatter::template_vars;
 use crate::renamer::IconConfig::*;
 use crate::renamer::IconStatus::*;
 use crate::renamer::{ConfigFile, Renamer};
@@ -13,8 +14,8 @@ type ListClass<'a> = Option<&'a [(regex::Regex, Icon)]>;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum IconConfig {
-    Class(Rule, Icon),
-    InitialClass(Rule, Icon),
+    Class(Rule, Icon, Captures),
+    InitialClass(Rule, Icon, Captures),
     TitleInClass(Rule, Icon, Captures),
     TitleInInitialClass(Rule, Icon, Captures),
     InitialTitleInClass(Rule, Icon, Captures),
@@ -36,10 +37,9 @@ impl IconConfig {
     pub fn get(&self) -> (Rule, Icon, Captures) {
         match &self {
             Default(icon) => ("DEFAULT".to_string(), icon.to_string(), None),
-            Class(rule, icon) | InitialClass(rule, icon) => {
-                (rule.to_string(), icon.to_string(), None)
-            }
-            TitleInClass(rule, icon, captures)
+            Class(rule, icon, captures)
+            | InitialClass(rule, icon, captures)
+            | TitleInClass(rule, icon, captures)
             | TitleInInitialClass(rule, icon, captures)
             | InitialTitleInClass(rule, icon, captures)
             | InitialTitleInInitialClass(rule, icon, captures) => {
@@ -47,6 +47,50 @@ impl IconConfig {
             }
         }
     }
+
+    fn map(self, f: impl FnOnce(Icon, Captures) -> (Icon, Captures)) -> Self {
+        let (variant, rule, icon, captures): (fn(Rule, Icon, Captures) -> Self, _, _, _) =
+            match self {
+                Default(icon) => return Default(f(icon, None).0),
+                Class(rule, icon, captures) => (Class, rule, icon, captures),
+                InitialClass(rule, icon, captures) => (InitialClass, rule, icon, captures),
+                TitleInClass(rule, icon, captures) => (TitleInClass, rule, icon, captures),
+                TitleInInitialClass(rule, icon, captures) => {
+                    (TitleInInitialClass, rule, icon, captures)
+                }
+                InitialTitleInClass(rule, icon, captures) => {
+                    (InitialTitleInClass, rule, icon, captures)
+                }
+                InitialTitleInInitialClass(rule, icon, captures) => {
+                    (InitialTitleInInitialClass, rule, icon, captures)
+                }
+            };
+        let (icon, captures) = f(icon, captures);
+        variant(rule, icon, captures)
+    }
+
+    /// Drops the positional captures of a class rule when its icon does not use
+    /// them, so a `(kitty|alacritty)` rule still deduplicates. Named captures
+    /// are kept, the client formatters may use them.
+    fn without_unused_captures(self) -> Self {
+        match self {
+            Class(..) | InitialClass(..) => self.map(|icon, captures| {
+                let used = template_vars(&icon);
+                let captures = captures.filter(|captures| {
+                    captures
+                        .keys()
+                        .any(|k| used.contains(k) || !is_positional_capture(k))
+                });
+                (icon, captures)
+            }),
+            config => config,
+        }
+    }
+}
+
+fn is_positional_capture(name: &str) -> bool {
+    name.strip_prefix("match")
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -67,6 +111,44 @@ impl IconStatus {
             Active(config) | Inactive(config) => config.captures(),
         }
     }
+
+    /// The rule and its captures, without the icon.
+    pub fn rule(&self) -> (Rule, Captures) {
+        match self {
+            Active(config) | Inactive(config) => {
+                let (rule, _, captures) = config.get();
+                (rule, captures)
+            }
+        }
+    }
+
+    /// In an active icon, `{icon}` is the inactive icon, like in `client_active`,
+    /// with the captures of the inactive rule it may refer to.
+    fn with_inactive_icon(self, inactive: &IconStatus) -> Self {
+        match self {
+            Active(config) => {
+                let (inactive_icon, inactive_captures) = (inactive.icon(), inactive.captures());
+                Active(config.map(|icon, captures| {
+                    let captures = match (inactive_captures, captures) {
+                        (Some(mut merged), Some(captures)) => {
+                            merged.extend(captures);
+                            Some(merged)
+                        }
+                        (merged, captures) => captures.or(merged),
+                    };
+                    (icon.replace("{icon}", &inactive_icon), captures)
+                }))
+            }
+            status => status,
+        }
+    }
+
+    fn without_unused_captures(self) -> Self {
+        match self {
+            Active(config) => Active(config.without_unused_captures()),
+            Inactive(config) => Inactive(config.without_unused_captures()),
+        }
+    }
 }
 
 impl Renamer {
@@ -203,11 +285,14 @@ impl Renamer {
             .find_icon("DEFAULT", "DEFAULT", "", "", true, config)
             .unwrap_or(icon_default.clone());
 
-        if is_active {
-            icon_active.unwrap_or(match icon {
-                Some(i) => i,
-                None => icon_default_active,
-            })
+        let status = if is_active {
+            let inactive = icon.clone().unwrap_or(icon_default.clone());
+            icon_active
+                .unwrap_or(match icon {
+                    Some(i) => i,
+                    None => icon_default_active,
+                })
+                .with_inactive_icon(&inactive)
         } else {
             icon.unwrap_or_else(|| {
                 if self.args.verbose {
@@ -215,7 +300,9 @@ impl Renamer {
                 }
                 icon_default
             })
-        }
+        };
+
+        status.without_unused_captures()
     }
 }
 
@@ -241,8 +328,8 @@ pub fn forge_icon_status(
         captures,
     ) {
         (None, None, None, None, None) => Default(icon),
-        (Some(_), None, None, None, None) => Class(rule, icon),
-        (None, None, Some(_), None, None) => InitialClass(rule, icon),
+        (Some(_), None, None, None, c) => Class(rule, icon, c),
+        (None, None, Some(_), None, c) => InitialClass(rule, icon, c),
         (Some(_), Some(_), None, None, c) => TitleInClass(rule, icon, c),
         (None, None, Some(_), Some(_), c) => InitialTitleInInitialClass(rule, icon, c),
         (None, Some(_), Some(_), None, c) => TitleInInitialClass(rule, icon, c),
@@ -273,7 +360,13 @@ fn find_icon_helper(
             list.iter()
                 .find(|(rule, _)| rule.is_match(the_class))
                 .map(|(rule, icon)| {
-                    forge_icon_status(is_active, rule.to_string(), icon.to_string(), params, None)
+                    forge_icon_status(
+                        is_active,
+                        rule.to_string(),
+                        icon.to_string(),
+                        params,
+                        get_captures(Some(the_class), rule),
+                    )
                 })
         }
         (None, Some(list)) => {
@@ -303,18 +396,20 @@ fn find_icon_helper(
     }
 }
 
-fn get_captures(title: Option<&str>, rule: &regex::Regex) -> Captures {
-    match title {
-        Some(t) => rule.captures(t).map(|re_captures| {
+/// Captures as `match0`, `match1`... and by name for `(?P<name>...)` groups.
+fn get_captures(value: Option<&str>, rule: &regex::Regex) -> Captures {
+    match value {
+        Some(v) => rule.captures(v).map(|re_captures| {
             re_captures
                 .iter()
+                .zip(rule.capture_names())
                 .enumerate()
-                .map(|(k, v)| {
-                    (
-                        format!("match{k}"),
-                        v.map_or("", |m| m.as_str()).to_string(),
-                    )
+                .flat_map(|(k, (v, name))| {
+                    let v = v.map_or("", |m| m.as_str()).to_string();
+                    let named = name.map(|name| (name.to_string(), v.clone()));
+                    [Some((format!("match{k}"), v)), named]
                 })
+                .flatten()
                 .collect()
         }),
         _ => None,
@@ -1,32 +1,65 @@
+#[cfg(feature = "dbus")]
+mod dbus;
+mod filters;
 mod formatter;
 mod icon;
+mod output;
 
 #[macro_use]
 mod macros;
 
-use crate::config::{Config, ConfigFile, ConfigFormatRaw};
+use crate::config::{ClientSort, Config, ConfigFile, ConfigFormatRaw, OutputKind, WorkspaceKey};
 use crate::params::Args;
 use formatter::*;
-use hyprland::data::{Client, Clients, FullscreenMode, Workspace};
+pub use formatter::{validate_format, validate_template};
+use hyprland::data::{Client, Clients, FullscreenMode, Monitor, Monitors, Workspace, Workspaces};
 use hyprland::dispatch::*;
 use hyprland::event_listener::{EventListener, WorkspaceEventData};
 use hyprland::prelude::*;
 use hyprland::shared::Address;
 use icon::{IconConfig, IconStatus};
 use inotify::{Inotify, WatchMask};
+use output::workspaces_state;
+use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+type PositionedClient = (AppClient, ClientOrder);
+
+/// What the clients of a workspace are sorted by, besides their class and icon.
+struct ClientOrder {
+    at: (i16, i16),
+    focus_history_id: i8,
+    open_order: u64,
+}
+
 pub struct Renamer {
     known_workspaces: Mutex<HashSet<i32>>,
     cfg: Mutex<Config>,
     args: Args,
     workspace_strings_cache: Mutex<HashMap<i32, String>>,
+    /// The names of the workspaces when first seen, before being renamed.
+    workspace_names: Mutex<HashMap<i32, String>>,
+    /// The `defaultName` of the Hyprland workspace rules.
+    default_names: Mutex<HashMap<i32, String>>,
+    /// The order the clients were first seen in, by address.
+    open_order: Mutex<HashMap<String, u64>>,
+    /// The clients asking for attention, by address, until focused.
+    urgent: Mutex<HashSet<String>>,
+    output_cache: Mutex<HashMap<usize, String>>,
+    paused: AtomicBool,
+    #[cfg(feature = "dbus")]
+    dbus: Mutex<Option<zbus::blocking::Connection>>,
+    /// The last state published on D-Bus.
+    #[cfg(feature = "dbus")]
+    dbus_state: Mutex<output::State>,
 }
 
-#[derive(Clone, Eq, Debug)]
+#[derive(Clone, Debug)]
 pub struct AppClient {
     class: String,
     title: String,
@@ -38,25 +71,29 @@ pub struct AppClient {
     initial_title: String,
     is_active: bool,
     is_fullscreen: FullscreenMode,
-    is_dedup_inactive_fullscreen: bool,
     matched_rule: IconStatus,
+    flags: ClientFlags,
 }
 
-impl PartialEq for AppClient {
-    fn eq(&self, other: &Self) -> bool {
-        self.matched_rule == other.matched_rule
-            && self.is_active == other.is_active
-            && (self.is_dedup_inactive_fullscreen || self.is_fullscreen == other.is_fullscreen)
-    }
+/// The state of a client, besides being active or fullscreen.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientFlags {
+    is_urgent: bool,
+    is_floating: bool,
+    is_pinned: bool,
+    is_xwayland: bool,
+    /// Not mapped by Hyprland
+    is_hidden: bool,
+    /// Replaced by the window it launched, like a terminal running mpv
+    is_swallowed: bool,
+    /// The last focused client of a workspace that is not focused
+    is_last_focused: bool,
+    /// The first address of its tabbed group, and the size of the group.
+    group: Option<(String, usize)>,
 }
 
 impl AppClient {
-    fn new(
-        client: Client,
-        is_active: bool,
-        is_dedup_inactive_fullscreen: bool,
-        matched_rule: IconStatus,
-    ) -> Self {
+    fn new(client: Client, is_active: bool, matched_rule: IconStatus, flags: ClientFlags) -> Self {
         AppClient {
             initial_class: client.initial_class,
             class: client.class,
@@ -64,8 +101,8 @@ impl AppClient {
             title: client.title,
             is_active,
             is_fullscreen: client.fullscreen,
-            is_dedup_inactive_fullscreen,
             matched_rule,
+            flags,
         }
     }
 }
@@ -77,37 +114,104 @@ impl Renamer {
             cfg: Mutex::new(cfg),
             args,
             workspace_strings_cache: Mutex::new(HashMap::new()),
+            workspace_names: Mutex::new(HashMap::new()),
+            default_names: Mutex::new(HashMap::new()),
+            open_order: Mutex::new(HashMap::new()),
+            urgent: Mutex::new(HashSet::new()),
+            output_cache: Mutex::new(HashMap::new()),
+            paused: AtomicBool::new(false),
+            #[cfg(feature = "dbus")]
+            dbus: Mutex::new(None),
+            #[cfg(feature = "dbus")]
+            dbus_state: Mutex::new(output::State { workspaces: vec![] }),
         })
     }
 
     pub fn rename_workspace(&self) -> Result<(), Box<dyn Error + '_>> {
+        if self.paused.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
         // Config
         let config = &self.cfg.lock()?.config.clone();
 
-        // Rename active workspace if empty
-        rename_empty_workspace(config);
+        // Monitors and workspaces as seen by Hyprland
+        let mut hyprland_workspaces = HyprlandWorkspaces::get();
+        hyprland_workspaces.original_names = self.original_names(&hyprland_workspaces)?;
 
         // Filter clients
-        let clients = get_filtered_clients(config);
+        let clients = get_filtered_clients(config)?;
 
         // Get the active client
         let active_client = get_active_client();
 
         // Get workspaces based on open clients
-        let workspaces = self.get_workspaces_from_clients(clients, active_client, config)?;
-        let workspace_ids: HashSet<_> = workspaces.iter().map(|w| w.id).collect();
-
-        // Generate workspace strings
-        let workspaces_strings = self.generate_workspaces_string(workspaces, config);
-
-        // Filter out unchanged workspaces
-        let altered_workspaces = self.get_altered_workspaces(&workspaces_strings)?;
-
-        altered_workspaces.iter().for_each(|(&id, clients)| {
-            rename_cmd(id, clients, &config.format, &config.workspaces_name);
-        });
-
-        self.update_cache(&altered_workspaces, &workspace_ids)?;
+        let all_workspaces =
+            self.get_workspaces_from_clients(clients, active_client, &hyprland_workspaces, config)?;
+        let known_workspaces = self.known_workspaces.lock()?.clone();
+
+        for (index, output) in config.output.iter().enumerate() {
+            let is_rename = output.kind == OutputKind::HyprlandRename;
+
+            // Empty workspaces never seen with clients are left untouched, except the active one
+            let workspaces: Vec<AppWorkspace> = all_workspaces
+                .iter()
+                .filter(|workspace| {
+                    output.format.rename_empty_workspaces
+                        || workspace.is_active
+                        || known_workspaces.contains(&workspace.id)
+                })
+                .filter(|workspace| {
+                    !(is_rename && config.is_skipped(workspace.id, &workspace.name))
+                })
+                .map(|workspace| workspace.without_hidden_clients(&output.format))
+                .collect();
+
+            // Generate workspace strings
+            let workspaces_strings =
+                self.generate_workspaces_string(workspaces.clone(), &output.format);
+
+            if is_rename {
+                let names: HashMap<i32, String> = workspaces
+                    .iter()
+                    .map(|workspace| {
+                        let clients = workspaces_strings
+                            .get(&workspace.id)
+                            .map_or("", |s| s.as_str());
+                        let name = workspace_name(
+                            workspace,
+                            clients,
+                            &output.format,
+                            &config.workspaces_name,
+                        );
+                        (workspace.id, name)
+                    })
+                    .collect();
+
+                self.forget_own_names(&names)?;
+
+                // Filter out unchanged workspaces
+                let altered_workspaces = self.get_altered_workspaces(&names)?;
+
+                altered_workspaces
+                    .iter()
+                    .for_each(|(&id, name)| rename_cmd(id, name));
+
+                let renamed_ids = workspaces.iter().map(|w| w.id).collect();
+                self.update_cache(&altered_workspaces, &renamed_ids)?;
+            } else {
+                let state = workspaces_state(
+                    &workspaces,
+                    &workspaces_strings,
+                    &output.format,
+                    &config.workspaces_name,
+                );
+
+                if let Err(e) = self.write_output(index, output, &state) {
+                    println!("Unable to write {:?} output: {e:?}", output.kind);
+                }
+            }
+        }
 
         Ok(())
     }
@@ -149,62 +253,154 @@ impl Renamer {
         &self,
         clients: Vec<Client>,
         active_client: String,
+        hyprland_workspaces: &HyprlandWorkspaces,
         config: &ConfigFile,
     ) -> Result<Vec<AppWorkspace>, Box<dyn Error + '_>> {
-        let mut workspaces: HashMap<i32, Vec<(AppClient, (i16, i16))>> = self
+        let mut workspaces: HashMap<i32, Vec<PositionedClient>> = self
             .known_workspaces
             .lock()?
             .iter()
+            .chain(hyprland_workspaces.workspaces.keys())
             .map(|&i| (i, Vec::new()))
             .collect();
 
-        let is_dedup_inactive_fullscreen = config.format.dedup_inactive_fullscreen;
+        // Forget the closed clients, the others keep their order
+        let addresses: HashSet<String> = clients.iter().map(|c| c.address.to_string()).collect();
+        let mut open_order = self.open_order.lock()?;
+        open_order.retain(|address, _| addresses.contains(address));
+        let swallowed: HashSet<String> = clients
+            .iter()
+            .filter_map(|c| c.swallowing.as_ref().map(|address| address.to_string()))
+            .filter(|address| address != "0x0")
+            .collect();
+        let mut urgent = self.urgent.lock()?;
+        urgent.retain(|address| addresses.contains(address) && *address != active_client);
 
         for client in clients {
             let workspace_id = client.workspace.id;
             self.known_workspaces.lock()?.insert(workspace_id);
-            let is_active = active_client == client.address.to_string();
-            workspaces
-                .entry(workspace_id)
-                .or_insert_with(Vec::new)
-                .push((
-                    AppClient::new(
-                        client.clone(),
+            let address = client.address.to_string();
+            let is_active = active_client == address;
+            let next_order = open_order.values().max().map_or(0, |order| order + 1);
+            let flags = ClientFlags {
+                is_urgent: urgent.contains(&address),
+                is_floating: client.floating,
+                is_pinned: client.pinned,
+                is_xwayland: client.xwayland,
+                is_hidden: !client.mapped,
+                is_swallowed: swallowed.contains(&address),
+                is_last_focused: false,
+                group: client
+                    .grouped
+                    .iter()
+                    .map(|address| address.to_string())
+                    .min()
+                    .map(|first| (first, client.grouped.len())),
+            };
+            let order = ClientOrder {
+                at: client.at,
+                focus_history_id: client.focus_history_id,
+                open_order: *open_order.entry(address).or_insert(next_order),
+            };
+            workspaces.entry(workspace_id).or_default().push((
+                AppClient::new(
+                    client.clone(),
+                    is_active,
+                    self.parse_icon(
+                        client.initial_class,
+                        client.class,
+                        client.initial_title,
+                        client.title,
                         is_active,
-                        is_dedup_inactive_fullscreen,
-                        self.parse_icon(
-                            client.initial_class,
-                            client.class,
-                            client.initial_title,
-                            client.title,
-                            is_active,
-                            config,
-                        ),
+                        config,
                     ),
-                    client.at,
-                ));
+                    flags,
+                ),
+                order,
+            ));
         }
 
         Ok(workspaces
             .into_iter()
             .map(|(id, mut clients)| {
-                clients.sort_by(|a, b| a.1 .0.cmp(&b.1 .0).then_with(|| a.1 .1.cmp(&b.1 .1)));
+                if hyprland_workspaces.active != Some(id) {
+                    mark_last_focused(&mut clients);
+                }
+                sort_clients(&mut clients, config);
 
                 let clients = clients.into_iter().map(|(client, _)| client).collect();
 
-                AppWorkspace::new(id, clients)
+                hyprland_workspaces.app_workspace(id, clients)
             })
             .collect())
     }
 
-    pub fn reset_workspaces(&self, config: ConfigFile) -> Result<(), Box<dyn Error + '_>> {
+    /// Gives back to the workspaces the names they had before being renamed,
+    /// with the current config, reloaded or not.
+    pub fn reset_workspaces(&self) -> Result<(), Box<dyn Error + '_>> {
         self.workspace_strings_cache.lock()?.clear();
+        let config = self.cfg.lock()?.config.clone();
+
+        if config.rename_output().is_some() {
+            let original_names = self.original_names(&HyprlandWorkspaces::default())?;
+            let known_workspaces = self.known_workspaces.lock()?;
+
+            known_workspaces
+                .iter()
+                .chain(original_names.keys())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .for_each(|id| {
+                    let name = original_names
+                        .get(id)
+                        .cloned()
+                        .unwrap_or_else(|| id.to_string());
+                    if !config.is_skipped(*id, &name) {
+                        rename_cmd(*id, &name);
+                    }
+                });
+        }
 
-        self.known_workspaces
-            .lock()?
-            .iter()
-            .for_each(|&id| rename_cmd(id, "", &config.format, &config.workspaces_name));
+        Ok(())
+    }
+
+    /// Remembers the names of the workspaces seen for the first time,
+    /// and returns all the original names, the workspace rules `defaultName` first.
+    fn original_names(
+        &self,
+        hyprland_workspaces: &HyprlandWorkspaces,
+    ) -> Result<HashMap<i32, String>, Box<dyn Error + '_>> {
+        let mut workspace_names = self.workspace_names.lock()?;
+        for workspace in hyprland_workspaces.workspaces.values() {
+            workspace_names
+                .entry(workspace.id)
+                .or_insert_with(|| workspace.name.clone());
+        }
 
+        let mut original_names = workspace_names.clone();
+        original_names.extend(self.default_names.lock()?.clone());
+
+        Ok(original_names)
+    }
+
+    /// Reads the `defaultName` of the workspace rules, the workspaces may already be renamed.
+    /// On error, the names read before are kept.
+    pub fn load_default_names(&self) -> Result<(), Box<dyn Error + '_>> {
+        let default_names = get_default_names()?;
+        *self.default_names.lock()? = default_names;
+        Ok(())
+    }
+
+    /// Forgets the original names which are the names about to be given, never given by
+    /// this instance: they were left by a previous one, stopped without resetting them.
+    fn forget_own_names(&self, names: &HashMap<i32, String>) -> Result<(), Box<dyn Error + '_>> {
+        let cache = self.workspace_strings_cache.lock()?;
+        let mut workspace_names = self.workspace_names.lock()?;
+        for (&id, name) in names {
+            if !cache.contains_key(&id) && workspace_names.get(&id) == Some(name) {
+                workspace_names.insert(id, id.to_string());
+            }
+        }
         Ok(())
     }
 
@@ -222,9 +418,24 @@ impl Renamer {
             add_workspace_moved_handler,
             add_workspace_changed_handler,
             add_fullscreen_state_changed_handler,
-            add_window_title_changed_handler
+            add_window_title_changed_handler,
+            add_active_monitor_changed_handler
         );
 
+        let this = self.clone();
+        event_listener.add_urgent_state_changed_handler(move |address| {
+            _ = this.set_urgent(address);
+            _ = this.rename_workspace();
+        });
+
+        let this = self.clone();
+        event_listener.add_config_reloaded_handler(move || {
+            if let Err(e) = this.load_default_names() {
+                eprintln!("Unable to read the workspace rules: {e}");
+            }
+            _ = this.rename_workspace();
+        });
+
         let this = self.clone();
         event_listener.add_workspace_deleted_handler(move |wt| {
             _ = this.rename_workspace();
@@ -235,7 +446,7 @@ impl Renamer {
     }
 
     pub fn watch_config_changes(
-        &self,
+        self: &Arc<Self>,
         cfg_path: Option<PathBuf>,
     ) -> Result<(), Box<dyn Error + '_>> {
         match &cfg_path {
@@ -249,13 +460,7 @@ impl Renamer {
                     notify.read_events_blocking(&mut buffer)?.last();
 
                     println!("Reloading config !");
-                    // Clojure to force quick release of lock
-                    {
-                        match Config::new(cfg_path.clone(), false, false) {
-                            Ok(config) => self.cfg.lock()?.config = config.config,
-                            Err(err) => println!("Unable to reload config: {err:?}"),
-                        }
-                    }
+                    self.reload_config()?;
 
                     // Handle event
                     // Run on window events
@@ -266,67 +471,292 @@ impl Renamer {
         }
     }
 
+    pub fn reload_config(self: &Arc<Self>) -> Result<(), Box<dyn Error + '_>> {
+        let cfg_path = self.cfg.lock()?.cfg_path.clone();
+        if let Some(cfg_path) = cfg_path {
+            match Config::new(cfg_path, false, false) {
+                Ok(config) => {
+                    self.cfg.lock()?.config = config.config;
+                    self.output_cache.lock()?.clear();
+                    #[cfg(feature = "dbus")]
+                    if let Err(e) = self.update_dbus() {
+                        println!("Unable to update the D-Bus service: {e:?}");
+                    }
+                }
+                Err(err) => println!("Unable to reload config: {err:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    fn set_urgent(&self, address: Address) -> Result<(), Box<dyn Error + '_>> {
+        self.urgent.lock()?.insert(address.to_string());
+        Ok(())
+    }
+
     fn remove_workspace(&self, wt: WorkspaceEventData) -> Result<bool, Box<dyn Error + '_>> {
+        self.workspace_names.lock()?.remove(&wt.id);
         Ok(self.known_workspaces.lock()?.remove(&wt.id))
     }
 }
 
-fn rename_empty_workspace(config: &ConfigFile) {
-    _ = Workspace::get_active().map(|workspace| {
-        if workspace.windows == 0 {
-            rename_cmd(workspace.id, "", &config.format, &config.workspaces_name);
-        }
+/// Marks the most recently focused client, the one focused when switching to its workspace.
+fn mark_last_focused(clients: &mut [PositionedClient]) {
+    if let Some((client, _)) = clients
+        .iter_mut()
+        .filter(|(client, order)| {
+            order.focus_history_id >= 0 && !client.flags.is_hidden && !client.flags.is_swallowed
+        })
+        .min_by_key(|(_, order)| order.focus_history_id)
+    {
+        client.flags.is_last_focused = true;
+    }
+}
+
+/// Sorts the clients with `sort`, the `pin_first` classes first, then by position.
+fn sort_clients(clients: &mut [PositionedClient], config: &ConfigFile) {
+    let pin_rank = |client: &AppClient| {
+        config
+            .pin_first
+            .iter()
+            .position(|class| class.is_match(&client.class))
+            .unwrap_or(usize::MAX)
+    };
+
+    clients.sort_by(|(a, a_order), (b, b_order)| {
+        pin_rank(a)
+            .cmp(&pin_rank(b))
+            .then_with(|| match config.sort {
+                ClientSort::Position => std::cmp::Ordering::Equal,
+                ClientSort::FocusHistory => a_order.focus_history_id.cmp(&b_order.focus_history_id),
+                ClientSort::OpenOrder => a_order.open_order.cmp(&b_order.open_order),
+                ClientSort::Class => a.class.to_lowercase().cmp(&b.class.to_lowercase()),
+                ClientSort::Icon => a.matched_rule.icon().cmp(&b.matched_rule.icon()),
+            })
+            .then_with(|| a_order.at.cmp(&b_order.at))
     });
 }
 
-fn rename_cmd(
-    id: i32,
+/// What Hyprland knows about the workspaces, beside their clients.
+#[derive(Default)]
+struct HyprlandWorkspaces {
+    workspaces: HashMap<i32, Workspace>,
+    active: Option<i32>,
+    visible: HashSet<i32>,
+    original_names: HashMap<i32, String>,
+}
+
+impl HyprlandWorkspaces {
+    fn get() -> Self {
+        let workspaces = Workspaces::get()
+            .map(|workspaces| workspaces.into_iter().map(|w| (w.id, w)).collect())
+            .unwrap_or_default();
+        let monitors: Vec<Monitor> = Monitors::get()
+            .map(|monitors| monitors.into_iter().collect())
+            .unwrap_or_default();
+
+        HyprlandWorkspaces {
+            workspaces,
+            active: monitors
+                .iter()
+                .find(|monitor| monitor.focused)
+                .map(|monitor| monitor.active_workspace.id),
+            visible: monitors
+                .iter()
+                .flat_map(|monitor| [monitor.active_workspace.id, monitor.special_workspace.id])
+                .filter(|&id| id != 0)
+                .collect(),
+            original_names: HashMap::new(),
+        }
+    }
+
+    fn app_workspace(&self, id: i32, clients: Vec<AppClient>) -> AppWorkspace {
+        let workspace = self.workspaces.get(&id);
+        AppWorkspace {
+            name: self.original_names.get(&id).cloned().unwrap_or_default(),
+            monitor: workspace.map(|w| w.monitor.clone()).unwrap_or_default(),
+            monitor_id: workspace.map_or(0, |w| w.monitor_id),
+            last_window_title: workspace
+                .map(|w| w.last_window_title.clone())
+                .unwrap_or_default(),
+            is_active: self.active == Some(id),
+            is_visible: self.visible.contains(&id),
+            ..AppWorkspace::new(id, clients)
+        }
+    }
+}
+
+fn rename_cmd(id: i32, name: &str) {
+    let _ = hyprland::dispatch!(RenameWorkspace, id, Some(name));
+}
+
+/// The workspace name, or its id if it can't be formatted.
+fn workspace_name(
+    workspace: &AppWorkspace,
+    clients: &str,
+    config_format: &ConfigFormatRaw,
+    workspaces_name: &[(WorkspaceKey, String)],
+) -> String {
+    format_workspace_name(workspace, clients, config_format, workspaces_name).unwrap_or_else(|e| {
+        eprintln!("Unable to format workspace {}: {e}", workspace.id);
+        workspace.id.to_string()
+    })
+}
+
+fn format_workspace_name(
+    workspace: &AppWorkspace,
     clients: &str,
     config_format: &ConfigFormatRaw,
-    workspaces_name: &[(String, String)],
-) {
-    let workspace_fmt = &config_format.workspace.to_string();
-    let workspace_empty_fmt = &config_format.workspace_empty.to_string();
+    workspaces_name: &[(WorkspaceKey, String)],
+) -> Result<String, FormatError> {
+    let id = workspace.id;
+    let config_format = &workspace_format(config_format, id, &workspace.monitor);
+    let markup = config_format.output_markup;
     let id_two_digits = format!("{:02}", id);
-    let workspace_name = get_workspace_name(id, workspaces_name);
+    // From the range of ids of the monitor, not to change when other workspaces come and go
+    let local_id = match config_format.workspaces_per_monitor {
+        Some(per_monitor) if per_monitor > 0 && id > 0 => (id - 1) % per_monitor + 1,
+        _ => id,
+    };
+    let workspace_name = get_workspace_name(workspace, workspaces_name);
+    let special_name = workspace.special_name().unwrap_or_default();
+
+    let count = workspace.clients.len() as i32;
+    let unique_classes = workspace
+        .clients
+        .iter()
+        .map(|client| &client.class)
+        .collect::<HashSet<_>>()
+        .len();
+    let has_urgent = workspace
+        .clients
+        .iter()
+        .any(|client| client.flags.is_urgent);
+    let active_title = workspace
+        .clients
+        .iter()
+        .find(|client| client.is_active)
+        .map_or("", |client| client.title.as_str());
 
     let mut vars = HashMap::from([
         ("id".to_string(), id.to_string()),
         ("id_long".to_string(), id_two_digits),
-        ("name".to_string(), workspace_name),
-        ("delim".to_string(), config_format.delim.to_string()),
+        ("local_id".to_string(), local_id.to_string()),
+        ("name".to_string(), markup_template(&workspace_name, markup)),
+        (
+            "delim".to_string(),
+            markup_template(&config_format.delim, markup),
+        ),
+        ("count".to_string(), count.to_string()),
+        ("count_sup".to_string(), to_superscript(count)),
+        ("unique_classes".to_string(), unique_classes.to_string()),
+        (
+            "monitor".to_string(),
+            literal(&markup_value(&workspace.monitor, markup)),
+        ),
+        ("monitor_id".to_string(), workspace.monitor_id.to_string()),
+        (
+            "monitor_short".to_string(),
+            literal(&markup_value(&workspace.monitor_short(), markup)),
+        ),
+        (
+            "active_title".to_string(),
+            literal(&markup_value(active_title, markup)),
+        ),
+        (
+            "last_window_title".to_string(),
+            literal(&markup_value(&workspace.last_window_title, markup)),
+        ),
+        ("is_active".to_string(), workspace.is_active.to_string()),
+        ("is_visible".to_string(), workspace.is_visible.to_string()),
+        ("has_urgent".to_string(), has_urgent.to_string()),
+        (
+            "special_name".to_string(),
+            literal(&markup_value(special_name, markup)),
+        ),
+        (
+            "original_name".to_string(),
+            literal(&markup_value(&workspace.name, markup)),
+        ),
     ]);
 
-    vars.insert("clients".to_string(), clients.to_string());
-    let workspace = if !clients.is_empty() {
-        formatter(workspace_fmt, &vars)
-    } else {
-        formatter(workspace_empty_fmt, &vars)
+    vars.insert("clients".to_string(), literal(clients));
+
+    let workspace_fmt = match (
+        clients.is_empty(),
+        &config_format.workspace_special,
+        &config_format.workspace_urgent,
+        &config_format.workspace_active,
+        &config_format.workspace_visible,
+    ) {
+        (true, _, _, _, _) => &config_format.workspace_empty,
+        (false, Some(special), _, _, _) if workspace.special_name().is_some() => special,
+        (false, _, Some(urgent), _, _) if has_urgent => urgent,
+        (false, _, _, Some(active), _) if workspace.is_active => active,
+        (false, _, _, _, Some(visible)) if workspace.is_visible => visible,
+        (false, _, _, _, _) => &config_format.workspace,
     };
 
-    let _ = hyprland::dispatch!(RenameWorkspace, id, Some(workspace.trim()));
+    let name = formatter(&markup_template(workspace_fmt, markup), &vars, markup)?;
+
+    Ok(name.trim().to_string())
 }
 
-fn get_workspace_name(id: i32, workspaces_name: &[(String, String)]) -> String {
-    let default_workspace_name = id.to_string();
+/// The name from `[workspaces_name]`, by id, range of ids or original name (`special:term`).
+fn get_workspace_name(
+    workspace: &AppWorkspace,
+    workspaces_name: &[(WorkspaceKey, String)],
+) -> String {
+    let id = workspace.id.to_string();
+    let default_workspace_name = workspace.special_name().unwrap_or(&id);
     workspaces_name
         .iter()
-        .find_map(|(x, name)| {
-            if x.eq(&id.to_string()) {
-                Some(name)
-            } else {
-                None
-            }
-        })
-        .unwrap_or(&default_workspace_name)
+        .find(|(key, _)| key.matches(workspace.id, &workspace.name))
+        .map_or(default_workspace_name, |(_, name)| name.as_str())
         .to_string()
 }
 
-fn get_filtered_clients(config: &ConfigFile) -> Vec<Client> {
-    let binding = Clients::get().unwrap();
+#[derive(Deserialize)]
+struct WorkspaceRule {
+    #[serde(rename = "workspaceString")]
+    workspace_string: String,
+    #[serde(rename = "defaultName")]
+    default_name: Option<String>,
+}
+
+/// The `defaultName` of the workspace rules by id, not exposed by the hyprland crate.
+fn get_default_names() -> Result<HashMap<i32, String>, String> {
+    let output = Command::new("hyprctl")
+        .args(["workspacerules", "-j"])
+        .output()
+        .map_err(|e| format!("hyprctl workspacerules: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "hyprctl workspacerules: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    serde_json::from_slice::<Vec<WorkspaceRule>>(&output.stdout)
+        .map(parse_default_names)
+        .map_err(|e| format!("hyprctl workspacerules: {e}"))
+}
+
+fn parse_default_names(rules: Vec<WorkspaceRule>) -> HashMap<i32, String> {
+    rules
+        .into_iter()
+        .filter_map(|rule| {
+            let id = rule.workspace_string.trim().parse().ok()?;
+            rule.default_name.map(|name| (id, name))
+        })
+        .collect()
+}
+
+fn get_filtered_clients(config: &ConfigFile) -> hyprland::Result<Vec<Client>> {
+    let binding = Clients::get()?;
     let config_exclude = &config.exclude;
 
-    binding
+    Ok(binding
         .into_iter()
         .filter(|client| client.pid > 0)
         .filter(|client| {
@@ -334,7 +764,7 @@ fn get_filtered_clients(config: &ConfigFile) -> Vec<Client> {
                 class.is_match(&client.class) && (title.is_match(&client.title))
             })
         })
-        .collect::<Vec<Client>>()
+        .collect::<Vec<Client>>())
 }
 
 fn get_active_client() -> String {
@@ -353,79 +783,50 @@ mod tests {
     use crate::renamer::IconConfig::*;
     use crate::renamer::IconStatus::*;
 
-    #[test]
-    fn test_app_client_partial_eq() {
-        let client1 = AppClient {
-            initial_class: "kitty".to_string(),
-            class: "kitty".to_string(),
-            title: "~".to_string(),
-            is_active: false,
-            is_fullscreen: FullscreenMode::Fullscreen,
-            initial_title: "zsh".to_string(),
-            matched_rule: Inactive(Class("(kitty|alacritty)".to_string(), "term".to_string())),
-            is_dedup_inactive_fullscreen: false,
-        };
-
-        let client2 = AppClient {
-            initial_class: "alacritty".to_string(),
-            class: "alacritty".to_string(),
-            title: "xplr".to_string(),
-            initial_title: "zsh".to_string(),
-            is_active: false,
-            is_fullscreen: FullscreenMode::Fullscreen,
-            matched_rule: Inactive(Class("(kitty|alacritty)".to_string(), "term".to_string())),
-            is_dedup_inactive_fullscreen: false,
-        };
-
-        let client3 = AppClient {
-            initial_class: "kitty".to_string(),
-            class: "kitty".to_string(),
-            title: "".to_string(),
-            initial_title: "zsh".to_string(),
-            is_active: true,
-            is_fullscreen: FullscreenMode::None,
-            matched_rule: Active(Class("(kitty|alacritty)".to_string(), "term".to_string())),
-            is_dedup_inactive_fullscreen: false,
-        };
-
-        let client4 = AppClient {
-            initial_class: "alacritty".to_string(),
-            class: "alacritty".to_string(),
-            title: "".to_string(),
-            initial_title: "zsh".to_string(),
-            is_active: false,
-            is_fullscreen: FullscreenMode::Fullscreen,
-            matched_rule: Inactive(Class("(kitty|alacritty)".to_string(), "term".to_string())),
-            is_dedup_inactive_fullscreen: false,
-        };
-
-        let client5 = AppClient {
-            initial_class: "kitty".to_string(),
-            class: "kitty".to_string(),
-            title: "".to_string(),
-            initial_title: "zsh".to_string(),
-            is_active: false,
-            is_fullscreen: FullscreenMode::Fullscreen,
-            matched_rule: Inactive(Class("(kitty|alacritty)".to_string(), "term".to_string())),
-            is_dedup_inactive_fullscreen: false,
-        };
+    /// A renamer with default arguments.
+    pub(super) fn test_renamer(config: &ConfigFile) -> Arc<Renamer> {
+        Renamer::new(
+            Config {
+                cfg_path: None,
+                config: config.clone(),
+            },
+            Args {
+                verbose: false,
+                debug: false,
+                config: None,
+                dump: false,
+                migrate_config: false,
+            },
+        )
+    }
 
-        let client6 = AppClient {
-            initial_class: "alacritty".to_string(),
-            class: "alacritty".to_string(),
-            title: "".to_string(),
-            initial_title: "zsh".to_string(),
-            is_active: false,
-            is_fullscreen: FullscreenMode::None,
-            matched_rule: Inactive(Class("alacritty".to_string(), "term".to_string())),
-            is_dedup_inactive_fullscreen: false,
-        };
+    /// A client of class `icon`, matching an inactive class rule with the same icon,
+    /// so an active one goes through `client_active`.
+    fn test_client(
+        icon: &str,
+        is_active: bool,
+        is_fullscreen: FullscreenMode,
+        flags: ClientFlags,
+    ) -> AppClient {
+        AppClient {
+            initial_class: icon.to_string(),
+            class: icon.to_string(),
+            title: icon.to_string(),
+            initial_title: icon.to_string(),
+            is_active,
+            is_fullscreen,
+            matched_rule: Inactive(Class(icon.to_string(), icon.to_string(), None)),
+            flags,
+        }
+    }
 
-        assert_eq!(client1 == client2, true);
-        assert_eq!(client4 == client5, true);
-        assert_eq!(client1 == client4, true);
-        assert_eq!(client1 == client3, false);
-        assert_eq!(client5 == client6, false);
+    /// The clients string of a single workspace.
+    fn test_clients_string(
+        renamer: &Renamer,
+        clients: Vec<AppClient>,
+        format: &ConfigFormatRaw,
+    ) -> String {
+        renamer.generate_workspaces_string(vec![AppWorkspace::new(1, clients)], format)[&1].clone()
     }
 
     #[test]
@@ -455,9 +856,9 @@ mod tests {
         let expected = [(1, "term5".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         initial_class: "kitty".to_string(),
                         class: "kitty".to_string(),
@@ -473,7 +874,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -490,7 +891,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "alacritty".to_string(),
@@ -507,7 +908,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "alacritty".to_string(),
@@ -524,7 +925,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "alacritty".to_string(),
@@ -541,11 +942,11 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -591,9 +992,9 @@ mod tests {
         let expected = [(1, "Zsh #Zsh# *Zsh*".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         initial_class: "alacritty".to_string(),
                         class: "alacritty".to_string(),
@@ -609,7 +1010,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "alacritty".to_string(),
@@ -626,7 +1027,7 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -643,11 +1044,11 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
         assert_eq!(actual, expected);
     }
@@ -683,9 +1084,9 @@ mod tests {
         let expected = [(1, "term2 term3".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         class: "kitty".to_string(),
                         title: "kitty".to_string(),
@@ -701,7 +1102,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -718,7 +1119,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "alacritty".to_string(),
@@ -735,7 +1136,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "alacritty".to_string(),
@@ -752,7 +1153,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "alacritty".to_string(),
@@ -769,11 +1170,11 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -785,6 +1186,7 @@ mod tests {
         let expected = "¹²³⁴⁵⁶⁷⁸⁹⁰";
         let output = to_superscript(input);
         assert_eq!(expected, output);
+        assert_eq!(to_superscript(-98), "⁻⁹⁸");
     }
 
     #[test]
@@ -813,9 +1215,9 @@ mod tests {
             .collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         initial_class: "kitty".to_string(),
                         class: "kitty".to_string(),
@@ -831,7 +1233,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -848,7 +1250,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -865,7 +1267,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -882,7 +1284,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -899,11 +1301,11 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -936,9 +1338,9 @@ mod tests {
             .collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         initial_class: "kitty".to_string(),
                         class: "kitty".to_string(),
@@ -954,7 +1356,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -971,7 +1373,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -988,7 +1390,7 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1005,7 +1407,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1022,11 +1424,11 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -1060,9 +1462,9 @@ mod tests {
             .collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         initial_class: "kitty".to_string(),
                         class: "kitty".to_string(),
@@ -1078,7 +1480,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1095,7 +1497,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1112,7 +1514,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1129,7 +1531,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -1146,11 +1548,11 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -1184,9 +1586,9 @@ mod tests {
             .collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         class: "kitty".to_string(),
                         initial_class: "kitty".to_string(),
@@ -1202,7 +1604,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1219,7 +1621,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1236,7 +1638,7 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1253,7 +1655,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1270,11 +1672,11 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -1306,9 +1708,9 @@ mod tests {
         let expected = [(1, "term5".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         initial_class: "kitty".to_string(),
                         class: "kitty".to_string(),
@@ -1316,8 +1718,12 @@ mod tests {
                         initial_title: "kitty".to_string(),
                         is_active: false,
                         is_fullscreen: FullscreenMode::None,
-                        matched_rule: Inactive(Class("kitty".to_string(), "term".to_string())),
-                        is_dedup_inactive_fullscreen: false,
+                        matched_rule: Inactive(Class(
+                            "kitty".to_string(),
+                            "term".to_string(),
+                            None,
+                        )),
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -1326,8 +1732,12 @@ mod tests {
                         initial_title: "kitty".to_string(),
                         is_active: false,
                         is_fullscreen: FullscreenMode::None,
-                        matched_rule: Inactive(Class("kitty".to_string(), "term".to_string())),
-                        is_dedup_inactive_fullscreen: false,
+                        matched_rule: Inactive(Class(
+                            "kitty".to_string(),
+                            "term".to_string(),
+                            None,
+                        )),
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -1336,8 +1746,12 @@ mod tests {
                         initial_title: "kitty".to_string(),
                         is_active: false,
                         is_fullscreen: FullscreenMode::None,
-                        matched_rule: Inactive(Class("kitty".to_string(), "term".to_string())),
-                        is_dedup_inactive_fullscreen: false,
+                        matched_rule: Inactive(Class(
+                            "kitty".to_string(),
+                            "term".to_string(),
+                            None,
+                        )),
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -1346,8 +1760,12 @@ mod tests {
                         initial_title: "kitty".to_string(),
                         is_active: false,
                         is_fullscreen: FullscreenMode::None,
-                        matched_rule: Inactive(Class("kitty".to_string(), "term".to_string())),
-                        is_dedup_inactive_fullscreen: false,
+                        matched_rule: Inactive(Class(
+                            "kitty".to_string(),
+                            "term".to_string(),
+                            None,
+                        )),
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -1356,12 +1774,16 @@ mod tests {
                         initial_title: "kitty".to_string(),
                         is_active: false,
                         is_fullscreen: FullscreenMode::None,
-                        matched_rule: Inactive(Class("kitty".to_string(), "term".to_string())),
-                        is_dedup_inactive_fullscreen: false,
+                        matched_rule: Inactive(Class(
+                            "kitty".to_string(),
+                            "term".to_string(),
+                            None,
+                        )),
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -1396,9 +1818,9 @@ mod tests {
         let expected = [(1, "term4 *term*".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         class: "kitty".to_string(),
                         initial_class: "kitty".to_string(),
@@ -1414,7 +1836,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1431,7 +1853,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -1448,7 +1870,7 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -1465,7 +1887,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1482,11 +1904,11 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -1521,9 +1943,9 @@ mod tests {
         let expected = [(1, "term4 [term]".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         class: "kitty".to_string(),
                         initial_class: "kitty".to_string(),
@@ -1539,7 +1961,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1556,7 +1978,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1573,7 +1995,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1590,7 +2012,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1607,11 +2029,11 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -1649,9 +2071,9 @@ mod tests {
         let expected = [(1, "term4 [*term*]".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         class: "kitty".to_string(),
                         initial_class: "kitty".to_string(),
@@ -1667,7 +2089,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1684,7 +2106,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -1701,7 +2123,7 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "kitty".to_string(),
@@ -1718,7 +2140,7 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "kitty".to_string(),
@@ -1735,11 +2157,11 @@ mod tests {
                             false,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -1784,9 +2206,9 @@ mod tests {
         let expected = [(1, "KKK *a* DDD".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         initial_class: "kitty".to_string(),
                         class: "kitty".to_string(),
@@ -1802,7 +2224,7 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "alacritty".to_string(),
@@ -1819,7 +2241,7 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         class: "qute".to_string(),
@@ -1836,16 +2258,52 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_inactive_icon_in_active_icon() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.class = vec![
+            (Regex::new("kitty").unwrap(), "k".to_string()),
+            (Regex::new("alacritty").unwrap(), "a".to_string()),
+            (Regex::new("DEFAULT").unwrap(), "d".to_string()),
+        ];
+        config.class_active = vec![
+            (Regex::new("kitty").unwrap(), "<b>{icon}</b>".to_string()),
+            (Regex::new("alacritty").unwrap(), "A".to_string()),
+            (Regex::new("DEFAULT").unwrap(), "*{icon}*".to_string()),
+        ];
+
+        let renamer = test_renamer(&config);
+        let active_icon = |class: &str| {
+            renamer
+                .parse_icon(
+                    class.to_string(),
+                    class.to_string(),
+                    class.to_string(),
+                    class.to_string(),
+                    true,
+                    &config,
+                )
+                .icon()
+        };
+
+        // Unchanged without {icon}
+        assert_eq!(active_icon("alacritty"), "A");
+        // {icon} used to be the active icon itself, looping until aborted,
+        // it is now the inactive icon, the default one for unknown classes
+        assert_eq!(active_icon("kitty"), "<b>k</b>");
+        assert_eq!(active_icon("qute"), "*d*");
+    }
+
     #[test]
     fn test_no_class_but_title_icon() {
         let mut config = crate::config::read_config_file(None, false, false).unwrap();
@@ -1871,9 +2329,9 @@ mod tests {
         let expected = [(1, "spotify".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "".to_string(),
                     class: "".to_string(),
                     title: "spotify".to_string(),
@@ -1888,10 +2346,10 @@ mod tests {
                         false,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -1922,9 +2380,9 @@ mod tests {
         let expected = [(1, "osu".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "osu!".to_string(),
                     class: "osu!".to_string(),
                     title: "osu!".to_string(),
@@ -1939,10 +2397,10 @@ mod tests {
                         false,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -1984,9 +2442,9 @@ mod tests {
             .collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![
+            vec![AppWorkspace::new(
+                1,
+                vec![
                     AppClient {
                         initial_class: "fake-app-unknown".to_string(),
                         class: "fake-app-unknown".to_string(),
@@ -2002,7 +2460,7 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                     AppClient {
                         initial_class: "fake-app-unknown".to_string(),
@@ -2019,11 +2477,11 @@ mod tests {
                             true,
                             &config,
                         ),
-                        is_dedup_inactive_fullscreen: false,
+                        flags: ClientFlags::default(),
                     },
                 ],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -2055,9 +2513,9 @@ mod tests {
         let expected = [(1, "default active".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "kitty".to_string(),
                     class: "kitty".to_string(),
                     title: "~".to_string(),
@@ -2072,10 +2530,10 @@ mod tests {
                         true,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -2098,9 +2556,9 @@ mod tests {
         );
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "kitty".to_string(),
                     class: "kitty".to_string(),
                     initial_title: "zsh".to_string(),
@@ -2115,10 +2573,10 @@ mod tests {
                         true,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         // When no active default is configured, the inactive default is used
@@ -2163,16 +2621,16 @@ mod tests {
         let expected = [(1, "term2".to_string())].into_iter().collect();
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "kitty".to_string(),
                     class: "kitty".to_string(),
                     title: "~".to_string(),
                     initial_title: "zsh".to_string(),
                     is_active: false,
                     is_fullscreen: FullscreenMode::None,
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                     matched_rule: renamer.parse_icon(
                         "kitty".to_string(),
                         "kitty".to_string(),
@@ -2182,8 +2640,8 @@ mod tests {
                         &config,
                     ),
                 }],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -2208,9 +2666,9 @@ mod tests {
         );
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "kitty".to_string(),
                     class: "kitty".to_string(),
                     initial_title: "zsh".to_string(),
@@ -2225,10 +2683,10 @@ mod tests {
                         false,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         let expected = [(1, "term3".to_string())].into_iter().collect();
@@ -2255,9 +2713,9 @@ mod tests {
         );
 
         let actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "kitty".to_string(),
                     class: "kitty".to_string(),
                     initial_title: "zsh".to_string(),
@@ -2272,10 +2730,10 @@ mod tests {
                         false,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         let expected = [(1, "term4".to_string())].into_iter().collect();
@@ -2308,9 +2766,9 @@ mod tests {
         assert_eq!(renamer.workspace_strings_cache.lock().unwrap().len(), 0);
 
         let mut app_workspaces = vec![
-            AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "kitty".to_string(),
                     class: "kitty".to_string(),
                     title: "term1".to_string(),
@@ -2325,12 +2783,12 @@ mod tests {
                         false,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            },
-            AppWorkspace {
-                id: 2,
-                clients: vec![AppClient {
+            ),
+            AppWorkspace::new(
+                2,
+                vec![AppClient {
                     initial_class: "kitty".to_string(),
                     class: "kitty".to_string(),
                     title: "term2".to_string(),
@@ -2345,12 +2803,12 @@ mod tests {
                         false,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            },
+            ),
         ];
 
-        let strings = renamer.generate_workspaces_string(app_workspaces.clone(), &config);
+        let strings = renamer.generate_workspaces_string(app_workspaces.clone(), &config.format);
         // Update cache and rename workspaces
         let altered_strings = renamer.get_altered_workspaces(&strings).unwrap();
         assert_eq!(strings, altered_strings);
@@ -2371,9 +2829,9 @@ mod tests {
         let altered_strings2 = renamer.get_altered_workspaces(&strings).unwrap();
         assert!(altered_strings2.is_empty());
 
-        app_workspaces.push(AppWorkspace {
-            id: 3,
-            clients: vec![AppClient {
+        app_workspaces.push(AppWorkspace::new(
+            3,
+            vec![AppClient {
                 initial_class: "kitty".to_string(),
                 class: "kitty".to_string(),
                 title: "term3".to_string(),
@@ -2388,11 +2846,11 @@ mod tests {
                     false,
                     &config,
                 ),
-                is_dedup_inactive_fullscreen: false,
+                flags: ClientFlags::default(),
             }],
-        });
+        ));
 
-        let strings3 = renamer.generate_workspaces_string(app_workspaces.clone(), &config);
+        let strings3 = renamer.generate_workspaces_string(app_workspaces.clone(), &config.format);
         let altered_strings3 = renamer.get_altered_workspaces(&strings3).unwrap();
 
         // Only the new workspace should be altered
@@ -2405,9 +2863,9 @@ mod tests {
             .unwrap();
 
         // Generate different workspace set - should update cache
-        let app_workspaces2 = vec![AppWorkspace {
-            id: 4,
-            clients: vec![AppClient {
+        let app_workspaces2 = vec![AppWorkspace::new(
+            4,
+            vec![AppClient {
                 initial_class: "kitty".to_string(),
                 class: "kitty".to_string(),
                 title: "term3".to_string(), // Different title
@@ -2422,11 +2880,11 @@ mod tests {
                     false,
                     &config,
                 ),
-                is_dedup_inactive_fullscreen: false,
+                flags: ClientFlags::default(),
             }],
-        }];
+        )];
 
-        let strings3 = renamer.generate_workspaces_string(app_workspaces2.clone(), &config);
+        let strings3 = renamer.generate_workspaces_string(app_workspaces2.clone(), &config.format);
         let altered_strings3 = renamer.get_altered_workspaces(&strings3).unwrap();
         assert_eq!(strings3, altered_strings3);
 
@@ -2444,7 +2902,7 @@ mod tests {
         }
 
         // Test cache reset
-        renamer.reset_workspaces(config.clone()).unwrap();
+        renamer.reset_workspaces().unwrap();
         assert_eq!(renamer.workspace_strings_cache.lock().unwrap().len(), 0);
     }
 
@@ -2495,9 +2953,9 @@ mod tests {
             .collect();
 
         let mut actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "foot".to_string(),
                     class: "foot".to_string(),
                     initial_title: "zsh".to_string(),
@@ -2512,10 +2970,10 @@ mod tests {
                         false,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
@@ -2528,9 +2986,9 @@ mod tests {
         .collect();
 
         actual = renamer.generate_workspaces_string(
-            vec![AppWorkspace {
-                id: 1,
-                clients: vec![AppClient {
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
                     initial_class: "foot".to_string(),
                     class: "foot".to_string(),
                     initial_title: "zsh".to_string(),
@@ -2545,39 +3003,1127 @@ mod tests {
                         true,
                         &config,
                     ),
-                    is_dedup_inactive_fullscreen: false,
+                    flags: ClientFlags::default(),
                 }],
-            }],
-            &config,
+            )],
+            &config.format,
         );
 
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_workspace_vars_and_active_formatters() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.workspace =
+            "{id}:{count}{count_sup} {unique_classes} {monitor}/{monitor_id} {active_title}|{last_window_title}"
+                .to_string();
+        config.format.workspace_active = Some("[{id}] {is_active} {is_visible}".to_string());
+        config.format.workspace_visible = Some("({id}) {is_active} {is_visible}".to_string());
+
+        let client = |class: &str, title: &str, is_active: bool| AppClient {
+            initial_class: class.to_string(),
+            class: class.to_string(),
+            initial_title: title.to_string(),
+            title: title.to_string(),
+            is_active,
+            is_fullscreen: FullscreenMode::None,
+            matched_rule: Inactive(Default("d".to_string())),
+            flags: ClientFlags::default(),
+        };
+
+        let workspace = AppWorkspace {
+            monitor: "DP-1".to_string(),
+            monitor_id: 1,
+            last_window_title: "vim".to_string(),
+            ..AppWorkspace::new(
+                3,
+                vec![
+                    client("kitty", "zsh", false),
+                    client("kitty", "vim {a} & b", true),
+                    client("firefox", "Rust", false),
+                ],
+            )
+        };
+        let name = |workspace: &AppWorkspace| {
+            format_workspace_name(workspace, "d d d", &config.format, &[]).unwrap()
+        };
+
+        assert_eq!(name(&workspace), "3:3³ 2 DP-1/1 vim {a} &amp; b|vim");
+        assert_eq!(
+            name(&AppWorkspace {
+                is_visible: true,
+                ..workspace.clone()
+            }),
+            "(3) false true"
+        );
+        assert_eq!(
+            name(&AppWorkspace {
+                is_active: true,
+                is_visible: true,
+                ..workspace.clone()
+            }),
+            "[3] true true"
+        );
+    }
+
+    #[test]
+    fn test_special_workspace() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.workspace_special = Some("*{special_name}* {name}:{clients}".to_string());
+        let workspaces_name = vec![(
+            WorkspaceKey::parse("special:term").unwrap(),
+            "terminal".to_string(),
+        )];
+
+        let workspace = |id: i32, name: &str| AppWorkspace {
+            name: name.to_string(),
+            ..AppWorkspace::new(id, vec![])
+        };
+        let name = |workspace: &AppWorkspace| {
+            format_workspace_name(workspace, "a b", &config.format, &workspaces_name).unwrap()
+        };
+
+        assert_eq!(name(&workspace(-98, "special:term")), "*term* terminal:a b");
+        assert_eq!(name(&workspace(-97, "special:music")), "*music* music:a b");
+        assert_eq!(name(&workspace(2, "2")), "2: a b");
+        assert_eq!(
+            get_workspace_name(&workspace(3, "special:terminal"), &workspaces_name),
+            "3"
+        );
+    }
+
+    #[test]
+    fn test_original_name() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.workspace = "{original_name}:{clients}".to_string();
+        let workspace = AppWorkspace {
+            name: "code".to_string(),
+            ..AppWorkspace::new(3, vec![])
+        };
+
+        assert_eq!(
+            format_workspace_name(&workspace, "a", &config.format, &[]).unwrap(),
+            "code:a"
+        );
+
+        let rules: Vec<WorkspaceRule> = serde_json::from_str(
+            r#"[
+                {"workspaceString": "1", "defaultName": "web"},
+                {"workspaceString": "2"},
+                {"workspaceString": "name:code", "defaultName": "code"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_default_names(rules),
+            HashMap::from([(1, "web".to_string())])
+        );
+
+        // A name left by a previous instance is not an original name
+        let renamer = test_renamer(&config);
+        *renamer.workspace_names.lock().unwrap() = HashMap::from([
+            (1, "1: term".to_string()),
+            (2, "code".to_string()),
+            (3, "3: web".to_string()),
+        ]);
+        renamer
+            .workspace_strings_cache
+            .lock()
+            .unwrap()
+            .insert(3, "3: web".to_string());
+        let names = HashMap::from([
+            (1, "1: term".to_string()),
+            (2, "2: term".to_string()),
+            (3, "3: web".to_string()),
+        ]);
+        renamer.forget_own_names(&names).unwrap();
+        assert_eq!(
+            *renamer.workspace_names.lock().unwrap(),
+            HashMap::from([
+                (1, "1".to_string()),
+                (2, "code".to_string()),
+                (3, "3: web".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_format_by_monitor_and_workspace() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config
+            .class
+            .push((Regex::new("kitty").unwrap(), "term".to_string()));
+        config.format.monitor.insert(
+            "DP-1".to_string(),
+            toml::toml! {
+                client = "{icon} {title}"
+                max_clients = 1
+            },
+        );
+        config.format.workspaces.insert(
+            "10".to_string(),
+            toml::toml! {
+                workspace = "chat"
+                workspace_empty = "chat"
+            },
+        );
+        config
+            .format
+            .workspaces
+            .insert("2".to_string(), toml::toml! { delim = "|" });
+
+        let renamer = test_renamer(&config);
+
+        let client = |title: &str| AppClient {
+            initial_class: "kitty".to_string(),
+            class: "kitty".to_string(),
+            initial_title: title.to_string(),
+            title: title.to_string(),
+            is_active: false,
+            is_fullscreen: FullscreenMode::None,
+            matched_rule: renamer.parse_icon(
+                "kitty".to_string(),
+                "kitty".to_string(),
+                title.to_string(),
+                title.to_string(),
+                false,
+                &config,
+            ),
+            flags: ClientFlags::default(),
+        };
+
+        let on_monitor = |id: i32, monitor: &str| AppWorkspace {
+            monitor: monitor.to_string(),
+            ..AppWorkspace::new(id, vec![client("zsh"), client("htop")])
+        };
+
+        let workspaces = vec![
+            on_monitor(1, "eDP-1"),
+            // The monitor overrides, then the workspace ones
+            on_monitor(2, "DP-1"),
+            on_monitor(10, "DP-1"),
+        ];
+        let expected = [
+            (1, "term term".to_string()),
+            (2, "term zsh".to_string()),
+            (10, "term zsh".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let actual = renamer.generate_workspaces_string(workspaces, &config.format);
+        assert_eq!(actual, expected);
+
+        let name = |id: i32, monitor: &str, clients: &str| {
+            let workspace = AppWorkspace {
+                monitor: monitor.to_string(),
+                ..AppWorkspace::new(id, vec![])
+            };
+            format_workspace_name(&workspace, clients, &config.format, &[]).unwrap()
+        };
+        assert_eq!(name(10, "DP-1", "term zsh"), "chat");
+        assert_eq!(name(10, "eDP-1", ""), "chat");
+        assert_eq!(name(2, "DP-1", "term zsh"), "2:|term zsh");
+    }
+
+    #[test]
+    fn test_client_format_by_class() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config
+            .class
+            .push((Regex::new("(?i)kitty").unwrap(), "term".to_string()));
+        config
+            .class
+            .push((Regex::new("firefox").unwrap(), "browser".to_string()));
+        config.client_format.push((
+            Regex::new("(?i)kitty").unwrap(),
+            toml::toml! {
+                client = "{icon} {title|truncate(5)}"
+            },
+        ));
+
+        let renamer = test_renamer(&config);
+
+        let client = |class: &str, title: &str, is_active: bool| AppClient {
+            initial_class: class.to_string(),
+            class: class.to_string(),
+            initial_title: title.to_string(),
+            title: title.to_string(),
+            is_active,
+            is_fullscreen: FullscreenMode::None,
+            matched_rule: renamer.parse_icon(
+                class.to_string(),
+                class.to_string(),
+                title.to_string(),
+                title.to_string(),
+                is_active,
+                &config,
+            ),
+            flags: ClientFlags::default(),
+        };
+
+        let workspaces = vec![AppWorkspace::new(
+            1,
+            vec![
+                client("kitty", "htop -d 10", false),
+                client("firefox", "Rust", false),
+                client("kitty", "vim", true),
+            ],
+        )];
+        let expected = [(1, "term htop… browser *term* vim".to_string())]
+            .into_iter()
+            .collect();
+        let actual = renamer.generate_workspaces_string(workspaces, &config.format);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_named_captures_and_class_captures() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.class.push((
+            Regex::new(r"org\.gnome\.(?P<app>\w+)").unwrap(),
+            "{app}".to_string(),
+        ));
+        config.initial_class.push((
+            Regex::new(r"^(steam)_app_(\d+)$").unwrap(),
+            "{match1}:{match2}".to_string(),
+        ));
+        config.title_in_class.push((
+            Regex::new("(?i)foot").unwrap(),
+            vec![(
+                Regex::new(r"pacman: (?P<pkg>.+?)-\d").unwrap(),
+                "{pkg}|{match1}".to_string(),
+            )],
+        ));
+
+        let renamer = test_renamer(&config);
+
+        let client = |class: &str, title: &str| AppClient {
+            initial_class: class.to_string(),
+            class: class.to_string(),
+            initial_title: title.to_string(),
+            title: title.to_string(),
+            is_active: false,
+            is_fullscreen: FullscreenMode::None,
+            matched_rule: renamer.parse_icon(
+                class.to_string(),
+                class.to_string(),
+                title.to_string(),
+                title.to_string(),
+                false,
+                &config,
+            ),
+            flags: ClientFlags::default(),
+        };
+
+        let workspaces = vec![
+            AppWorkspace::new(1, vec![client("org.gnome.Nautilus", "Home")]),
+            AppWorkspace::new(2, vec![client("steam_app_42", "Game")]),
+            AppWorkspace::new(3, vec![client("foot", "pacman: rust-1.70")]),
+        ];
+        let expected = [
+            (1, "Nautilus".to_string()),
+            (2, "steam:42".to_string()),
+            (3, "rust|rust".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let actual = renamer.generate_workspaces_string(workspaces, &config.format);
+        assert_eq!(actual, expected);
+        // Captures are kept when used by a condition, and by their exact name
+        config.class.push((
+            Regex::new(r"^(?P<pkg>\w+)-git$").unwrap(),
+            "{?pkg}git{/}".to_string(),
+        ));
+        config
+            .class
+            .push((Regex::new("^(xterm)$").unwrap(), "x{match10}".to_string()));
+        let captures = |class: &str| {
+            renamer
+                .parse_icon(
+                    class.to_string(),
+                    class.to_string(),
+                    class.to_string(),
+                    class.to_string(),
+                    false,
+                    &config,
+                )
+                .captures()
+        };
+        assert!(captures("neovim-git").is_some());
+        assert_eq!(captures("xterm"), None);
+
+        // Named captures are kept for the client formatters and in active icons
+        config.class.insert(
+            0,
+            (
+                Regex::new(r"^(?P<term>foot|kitty)$").unwrap(),
+                "term".to_string(),
+            ),
+        );
+        config.class_active.push((
+            Regex::new(r"org\.gnome\..*").unwrap(),
+            "<b>{icon}</b>".to_string(),
+        ));
+        config.format.client = "{icon} {term}".to_string();
+        let client = |class: &str, is_active: bool| AppClient {
+            initial_class: class.to_string(),
+            class: class.to_string(),
+            initial_title: class.to_string(),
+            title: class.to_string(),
+            is_active,
+            is_fullscreen: FullscreenMode::None,
+            matched_rule: renamer.parse_icon(
+                class.to_string(),
+                class.to_string(),
+                class.to_string(),
+                class.to_string(),
+                is_active,
+                &config,
+            ),
+            flags: ClientFlags::default(),
+        };
+        let active = client("org.gnome.Nautilus", true);
+        assert_eq!(active.matched_rule.icon(), "<b>{app}</b>");
+        assert_eq!(
+            active.matched_rule.captures().unwrap().get("app"),
+            Some(&"Nautilus".to_string())
+        );
+        let mut config_format = config.format.clone();
+        config_format.client = "{icon}".to_string();
+        let workspaces = vec![AppWorkspace::new(1, vec![active])];
+        let expected = [(1, "<b>Nautilus</b>".to_string())].into_iter().collect();
+        let actual = renamer.generate_workspaces_string(workspaces, &config_format);
+        assert_eq!(actual, expected);
+        let workspaces = vec![AppWorkspace::new(1, vec![client("foot", false)])];
+        let expected = [(1, "term foot".to_string())].into_iter().collect();
+        let actual = renamer.generate_workspaces_string(workspaces, &config.format);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_active_icon_and_conditional_formatter() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config
+            .class
+            .push((Regex::new("kitty").unwrap(), "term".to_string()));
+        config
+            .class_active
+            .push((Regex::new("DEFAULT").unwrap(), "*{icon}*".to_string()));
+        config.format.dedup = true;
+        config.format.client = "{icon}{?counter>1}{counter_sup}{/}".to_string();
+        config.format.client_dup = "{client}".to_string();
+
+        let renamer = test_renamer(&config);
+
+        let client = |title: &str, is_active: bool| AppClient {
+            initial_class: "kitty".to_string(),
+            class: "kitty".to_string(),
+            initial_title: title.to_string(),
+            title: title.to_string(),
+            is_active,
+            is_fullscreen: FullscreenMode::None,
+            matched_rule: renamer.parse_icon(
+                "kitty".to_string(),
+                "kitty".to_string(),
+                title.to_string(),
+                title.to_string(),
+                is_active,
+                &config,
+            ),
+            flags: ClientFlags::default(),
+        };
+
+        // In the active icon, {icon} is the inactive one
+        let workspaces = vec![
+            AppWorkspace::new(1, vec![client("zsh", true)]),
+            AppWorkspace::new(2, vec![client("{a}", false), client("}", false)]),
+        ];
+        let expected = [(1, "*term*".to_string()), (2, "term²".to_string())]
+            .into_iter()
+            .collect();
+        let actual = renamer.generate_workspaces_string(workspaces, &config.format);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_markup_escaping_and_plain_output() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config
+            .class
+            .push((Regex::new("Slack").unwrap(), "{title}".to_string()));
+        config.format.client_active = "<b>{icon}</b>".to_string();
+
+        let renamer = test_renamer(&config);
+
+        let workspaces = vec![AppWorkspace::new(
+            1,
+            vec![AppClient {
+                initial_class: "Slack".to_string(),
+                class: "Slack".to_string(),
+                initial_title: "Slack".to_string(),
+                title: "Q&A <general>".to_string(),
+                is_active: true,
+                is_fullscreen: FullscreenMode::None,
+                matched_rule: renamer.parse_icon(
+                    "Slack".to_string(),
+                    "Slack".to_string(),
+                    "Slack".to_string(),
+                    "Q&A <general>".to_string(),
+                    true,
+                    &config,
+                ),
+                flags: ClientFlags::default(),
+            }],
+        )];
+
+        let expected = [(1, "<b>Q&amp;A &lt;general&gt;</b>".to_string())]
+            .into_iter()
+            .collect();
+        let actual = renamer.generate_workspaces_string(workspaces.clone(), &config.format);
+        assert_eq!(actual, expected);
+
+        let mut format = config.format.clone();
+        format.output_markup = crate::config::OutputMarkup::Plain;
+        let expected = [(1, "Q&A <general>".to_string())].into_iter().collect();
+        let actual = renamer.generate_workspaces_string(workspaces.clone(), &format);
+        assert_eq!(actual, expected);
+
+        // The delimiter between the clients is stripped too
+        format.delim = "<i>|</i>".to_string();
+        let mut clients = workspaces[0].clients.clone();
+        clients.push(clients[0].clone());
+        let expected = [(1, "Q&A <general>|Q&A <general>".to_string())]
+            .into_iter()
+            .collect();
+        let actual =
+            renamer.generate_workspaces_string(vec![AppWorkspace::new(1, clients)], &format);
+        assert_eq!(actual, expected);
+
+        config
+            .class
+            .insert(0, (Regex::new("Slack").unwrap(), "{title_raw}".to_string()));
+        let workspaces = vec![AppWorkspace::new(
+            1,
+            vec![AppClient {
+                matched_rule: renamer.parse_icon(
+                    "Slack".to_string(),
+                    "Slack".to_string(),
+                    "Slack".to_string(),
+                    "Q&A <general>".to_string(),
+                    true,
+                    &config,
+                ),
+                ..workspaces[0].clients[0].clone()
+            }],
+        )];
+        let expected = [(1, "<b>Q&A <general></b>".to_string())]
+            .into_iter()
+            .collect();
+        let actual = renamer.generate_workspaces_string(workspaces, &config.format);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_formatter_filters() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.class.push((
+            Regex::new("firefox").unwrap(),
+            "{title|replace(\" — Mozilla Firefox\", \"\")|truncate(8)|upper}".to_string(),
+        ));
+        config.format.client = "{icon}{delim}{class|pad(9, \".\")}".to_string();
+        config.format.workspace = "{id|pad(2)}:{delim}{clients}".to_string();
+
+        let renamer = test_renamer(&config);
+
+        let title = "Rust & Friends — Mozilla Firefox".to_string();
+        let actual = renamer.generate_workspaces_string(
+            vec![AppWorkspace::new(
+                1,
+                vec![AppClient {
+                    initial_class: "firefox".to_string(),
+                    class: "firefox".to_string(),
+                    initial_title: title.clone(),
+                    title: title.clone(),
+                    is_active: false,
+                    is_fullscreen: FullscreenMode::None,
+                    matched_rule: renamer.parse_icon(
+                        "firefox".to_string(),
+                        "firefox".to_string(),
+                        title.clone(),
+                        title.clone(),
+                        false,
+                        &config,
+                    ),
+                    flags: ClientFlags::default(),
+                }],
+            )],
+            &config.format,
+        );
+
+        let expected = [(1, "RUST &amp; … ..firefox".to_string())]
+            .into_iter()
+            .collect();
+        assert_eq!(actual, expected);
+
+        let name = format_workspace_name(
+            &AppWorkspace::new(1, vec![]),
+            &actual[&1],
+            &config.format,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(name, "01: RUST &amp; … ..firefox");
+
+        // The inactive icon of an active client goes through the filters of client_active
+        config.format.client = "{icon}".to_string();
+        config.format.client_active = "<b>{icon|upper|pad(6, \".\")}</b>".to_string();
+        let client = test_client("kitty", true, FullscreenMode::None, ClientFlags::default());
+        assert_eq!(
+            test_clients_string(&renamer, vec![client], &config.format),
+            "<b>.KITTY</b>"
+        );
+    }
+
+    fn hyprland_workspace(id: i32, monitor: &str) -> Workspace {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id.to_string(),
+            "monitor": monitor,
+            "monitorID": 0,
+            "windows": 0,
+            "hasfullscreen": false,
+            "lastwindow": "0x0",
+            "lastwindowtitle": "",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_local_id_and_monitor_short() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.workspace_empty = "{monitor_short}:{local_id}".to_string();
+        let mut hyprland_workspaces = HyprlandWorkspaces {
+            workspaces: [(1, "DP-1"), (2, "DP-1"), (11, "HDMI-A-1"), (13, "HDMI-A-1")]
+                .into_iter()
+                .map(|(id, monitor)| (id, hyprland_workspace(id, monitor)))
+                .collect(),
+            ..HyprlandWorkspaces::default()
+        };
+        let name = |id: i32, format: &ConfigFormatRaw| {
+            let workspace = hyprland_workspaces.app_workspace(id, vec![]);
+            format_workspace_name(&workspace, "", format, &[]).unwrap()
+        };
+
+        // Without a range of ids per monitor, the id itself
+        assert_eq!(name(2, &config.format), "D1:2");
+        assert_eq!(name(13, &config.format), "HA1:13");
+
+        config.format.workspaces_per_monitor = Some(10);
+        assert_eq!(name(13, &config.format), "HA1:3");
+        assert_eq!(name(2, &config.format), "D1:2");
+
+        // Unchanged when another workspace of the monitor is destroyed
+        hyprland_workspaces.workspaces.remove(&11);
+        let workspace = hyprland_workspaces.app_workspace(13, vec![]);
+        assert_eq!(
+            format_workspace_name(&workspace, "", &config.format, &[]).unwrap(),
+            "HA1:3"
+        );
+    }
+
+    #[test]
+    fn test_dedup_by_and_same_state() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.dedup = true;
+        config.format.client_dup = "{icon}{counter}".to_string();
+        let renamer = test_renamer(&config);
+
+        let client = |class: &str, rule: &str, icon: &str, is_active: bool| AppClient {
+            initial_class: class.to_string(),
+            class: class.to_string(),
+            title: rule.to_string(),
+            initial_title: rule.to_string(),
+            is_active,
+            is_fullscreen: FullscreenMode::None,
+            matched_rule: Inactive(TitleInClass(rule.to_string(), icon.to_string(), None)),
+            flags: ClientFlags::default(),
+        };
+        let generate = |format: &ConfigFormatRaw| {
+            let clients = vec![
+                client("firefox", "youtube", "yt", false),
+                client("firefox", "github", "gh", false),
+                client("kitty", "zsh", "term", true),
+                client("kitty", "vim", "term", false),
+            ];
+            renamer.generate_workspaces_string(vec![AppWorkspace::new(1, clients)], format)[&1]
+                .clone()
+        };
+
+        assert_eq!(generate(&config.format), "yt gh *term* term");
+
+        config.format.dedup_by = crate::config::DedupBy::Class;
+        assert_eq!(generate(&config.format), "yt2 *term* term");
+
+        // The active client counts its inactive duplicates
+        config.format.dedup_same_state = false;
+        assert_eq!(generate(&config.format), "yt2 *term*2");
+
+        config.format.dedup_by = crate::config::DedupBy::Icon;
+        assert_eq!(generate(&config.format), "yt gh *term*2");
+    }
+
+    #[test]
+    fn test_urgent_client_and_workspace() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.client_urgent = Some("!{client}".to_string());
+        config.format.workspace_urgent = Some("{id}{?has_urgent}!{/}:{delim}{clients}".to_string());
+        let renamer = test_renamer(&config);
+        let client = |icon: &str, is_urgent: bool| {
+            let flags = ClientFlags {
+                is_urgent,
+                ..ClientFlags::default()
+            };
+            test_client(icon, false, FullscreenMode::None, flags)
+        };
+
+        let workspaces = vec![
+            AppWorkspace::new(1, vec![client("chat", true), client("term", false)]),
+            AppWorkspace::new(2, vec![client("term", false)]),
+        ];
+        let strings = renamer.generate_workspaces_string(workspaces.clone(), &config.format);
+        let names: Vec<String> = workspaces
+            .iter()
+            .map(|w| format_workspace_name(w, &strings[&w.id], &config.format, &[]).unwrap())
+            .collect();
+        assert_eq!(names, ["1!: !chat term", "2: term"]);
+
+        // Without client_urgent, an urgent client is deduplicated as usual
+        config.format.dedup = true;
+        let clients = vec![client("term", true), client("term", false)];
+        assert_eq!(
+            test_clients_string(&renamer, clients.clone(), &config.format),
+            "!term term"
+        );
+        config.format.client_urgent = None;
+        assert_eq!(
+            test_clients_string(&renamer, clients, &config.format),
+            "term²"
+        );
+    }
+
+    #[test]
+    fn test_client_state_formatters() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.client_floating = Some("~{icon}".to_string());
+        config.format.client_pinned = Some("^{icon}".to_string());
+        config.format.client_xwayland = Some("{icon}x".to_string());
+        let renamer = test_renamer(&config);
+        let client = |icon: &str, is_active: bool, flags: ClientFlags| {
+            test_client(icon, is_active, FullscreenMode::None, flags)
+        };
+        let tab = || ClientFlags {
+            group: Some(("0x1".to_string(), 3)),
+            ..ClientFlags::default()
+        };
+        let floating = ClientFlags {
+            is_floating: true,
+            ..ClientFlags::default()
+        };
+        let pinned = ClientFlags {
+            is_pinned: true,
+            ..ClientFlags::default()
+        };
+        let xwayland = ClientFlags {
+            is_xwayland: true,
+            ..ClientFlags::default()
+        };
+        let clients = vec![
+            client("dialog", false, floating.clone()),
+            // Pinned windows are floating, client_pinned comes first
+            client(
+                "pip",
+                false,
+                ClientFlags {
+                    is_floating: true,
+                    ..pinned.clone()
+                },
+            ),
+            client("steam", false, xwayland),
+            client("a", false, tab()),
+            client("b", true, tab()),
+            client("c", false, tab()),
+        ];
+
+        assert_eq!(
+            test_clients_string(&renamer, clients.clone(), &config.format),
+            "~dialog ^pip steamx a *b* c"
+        );
+
+        // The group is shown once, with its active client
+        config.format.client_grouped = Some("[{icon}{group_count_sup}]".to_string());
+        assert_eq!(
+            test_clients_string(&renamer, clients, &config.format),
+            "~dialog ^pip steamx [*b*³]"
+        );
+
+        // The state formatters wrap the usual ones, keeping the counter and the active marker
+        config.format.dedup = true;
+        config.format.client_pinned = Some("^{client}".to_string());
+        let clients = vec![
+            client("pip", false, pinned.clone()),
+            client("pip", false, pinned.clone()),
+            client("mpv", true, pinned),
+        ];
+        assert_eq!(
+            test_clients_string(&renamer, clients, &config.format),
+            "^pip² ^*mpv*"
+        );
+
+        // A group keeps the place of its first client, even deduplicated
+        let clients = vec![
+            client("a", false, tab()),
+            client("firefox", false, ClientFlags::default()),
+            client("b", true, tab()),
+        ];
+        assert_eq!(
+            test_clients_string(&renamer, clients, &config.format),
+            "[*b*³] firefox"
+        );
+    }
+
+    #[test]
+    fn test_swallowed_and_hidden_clients() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        let renamer = test_renamer(&config);
+        let client = |icon: &str, is_active: bool, flags: ClientFlags| {
+            test_client(icon, is_active, FullscreenMode::None, flags)
+        };
+        let swallowed = ClientFlags {
+            is_swallowed: true,
+            ..ClientFlags::default()
+        };
+        let hidden = ClientFlags {
+            is_hidden: true,
+            ..ClientFlags::default()
+        };
+        let workspace = AppWorkspace::new(
+            1,
+            vec![
+                client("term", false, swallowed.clone()),
+                client("mpv", true, ClientFlags::default()),
+                client("ghost", false, hidden.clone()),
+            ],
+        );
+        let generate = |format: &ConfigFormatRaw| {
+            let clients = workspace.without_hidden_clients(format).clients;
+            test_clients_string(&renamer, clients, format)
+        };
+
+        assert_eq!(generate(&config.format), "*mpv*");
+
+        config.format.client_swallowed = Some("({icon})".to_string());
+        config.format.client_hidden = Some("_{icon}_".to_string());
+        assert_eq!(generate(&config.format), "(term) *mpv* _ghost_");
+
+        // Kept without their formatter, they are deduplicated as usual
+        config.format.dedup = true;
+        config.format.client_swallowed = None;
+        config.format.client_hidden = None;
+        let clients = vec![
+            client(
+                "term",
+                false,
+                ClientFlags {
+                    is_hidden: true,
+                    ..swallowed
+                },
+            ),
+            client("term", false, ClientFlags::default()),
+        ];
+        assert_eq!(
+            test_clients_string(&renamer, clients, &config.format),
+            "term²"
+        );
+    }
+
+    #[test]
+    fn test_maximized_and_fullscreen() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        let renamer = test_renamer(&config);
+        let clients = vec![
+            test_client(
+                "a",
+                false,
+                FullscreenMode::Maximized,
+                ClientFlags::default(),
+            ),
+            test_client(
+                "b",
+                false,
+                FullscreenMode::Fullscreen,
+                ClientFlags::default(),
+            ),
+        ];
+        let generate =
+            |format: &ConfigFormatRaw| test_clients_string(&renamer, clients.clone(), format);
+
+        // Without client_maximized, maximized clients use client_fullscreen
+        assert_eq!(generate(&config.format), "[a] [b]");
+
+        config.format.client_maximized = Some("({icon})".to_string());
+        assert_eq!(generate(&config.format), "(a) [b]");
+
+        config.format.dedup_inactive_fullscreen = true;
+        config.format.dedup_inactive_maximized = Some(false);
+        assert_eq!(generate(&config.format), "(a) b");
+    }
+
+    #[test]
+    fn test_max_clients_overflow() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.max_clients = Some(2);
+        config.format.client_overflow = Some("+{hidden_count}".to_string());
+        config.format.workspaces = HashMap::from([(
+            "5".to_string(),
+            toml::toml! { max_clients = 1 client_overflow = "{hidden_count_sup}" },
+        )]);
+        let renamer = test_renamer(&config);
+
+        let client = |icon: &str, is_active: bool| {
+            let rule = Class(icon.to_string(), icon.to_string(), None);
+            AppClient {
+                initial_class: icon.to_string(),
+                class: icon.to_string(),
+                title: icon.to_string(),
+                initial_title: icon.to_string(),
+                is_active,
+                is_fullscreen: FullscreenMode::None,
+                matched_rule: if is_active {
+                    Active(rule)
+                } else {
+                    Inactive(rule)
+                },
+                flags: ClientFlags::default(),
+            }
+        };
+        let clients = vec![client("a", false), client("b", false), client("c", true)];
+
+        let actual = renamer.generate_workspaces_string(
+            vec![
+                AppWorkspace::new(1, clients.clone()),
+                AppWorkspace::new(2, clients[..2].to_vec()),
+                AppWorkspace::new(5, clients.clone()),
+            ],
+            &config.format,
+        );
+
+        // The active client replaces the last shown one
+        assert_eq!(actual[&1], "a c +1");
+        assert_eq!(actual[&2], "a b");
+        assert_eq!(actual[&5], "c ²");
+
+        // Even without any room, the active client is shown
+        config.format.max_clients = Some(0);
+        let actual = renamer.generate_workspaces_string(
+            vec![
+                AppWorkspace::new(1, clients.clone()),
+                AppWorkspace::new(2, clients[..2].to_vec()),
+            ],
+            &config.format,
+        );
+        assert_eq!(actual[&1], "c +2");
+        assert_eq!(actual[&2], "+2");
+    }
+
+    #[test]
+    fn test_sort_clients() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        let client = |class: &str, icon: &str, at: i16, focus_history_id: i8, open_order| {
+            (
+                AppClient {
+                    initial_class: class.to_string(),
+                    class: class.to_string(),
+                    title: class.to_string(),
+                    initial_title: class.to_string(),
+                    is_active: false,
+                    is_fullscreen: FullscreenMode::None,
+                    matched_rule: Inactive(Class(class.to_string(), icon.to_string(), None)),
+                    flags: ClientFlags::default(),
+                },
+                ClientOrder {
+                    at: (at, 0),
+                    focus_history_id,
+                    open_order,
+                },
+            )
+        };
+        let sorted = |config: &ConfigFile| {
+            let mut clients = vec![
+                client("kitty", "b", 0, 2, 1),
+                client("Firefox", "c", 10, 0, 2),
+                client("code", "a", 20, 1, 0),
+            ];
+            sort_clients(&mut clients, config);
+            clients
+                .into_iter()
+                .map(|(client, _)| client.class)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(&config), ["kitty", "Firefox", "code"]);
+        config.sort = ClientSort::FocusHistory;
+        assert_eq!(sorted(&config), ["Firefox", "code", "kitty"]);
+        config.sort = ClientSort::OpenOrder;
+        assert_eq!(sorted(&config), ["code", "kitty", "Firefox"]);
+        config.sort = ClientSort::Class;
+        assert_eq!(sorted(&config), ["code", "Firefox", "kitty"]);
+        config.sort = ClientSort::Icon;
+        assert_eq!(sorted(&config), ["code", "kitty", "Firefox"]);
+        config.pin_first = vec![Regex::new("(?i)firefox").unwrap()];
+        assert_eq!(sorted(&config), ["Firefox", "code", "kitty"]);
+    }
+
+    #[test]
+    fn test_last_focused_client() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.client_last_focused = Some("_{icon}_".to_string());
+        let renamer = test_renamer(&config);
+        let client = |class: &str, focus_history_id: i8, flags: ClientFlags| {
+            let order = ClientOrder {
+                at: (0, 0),
+                focus_history_id,
+                open_order: 0,
+            };
+            (
+                test_client(class, false, FullscreenMode::None, flags),
+                order,
+            )
+        };
+        let marked = |mut clients: Vec<(AppClient, ClientOrder)>| {
+            mark_last_focused(&mut clients);
+            clients
+                .into_iter()
+                .map(|(client, _)| client)
+                .collect::<Vec<_>>()
+        };
+
+        // The swallowed terminal can't get the focus back
+        let swallowed = ClientFlags {
+            is_swallowed: true,
+            ..ClientFlags::default()
+        };
+        let clients = marked(vec![
+            client("code", 3, ClientFlags::default()),
+            client("mpv", 2, ClientFlags::default()),
+            client("term", 1, swallowed),
+        ]);
+        assert_eq!(
+            clients
+                .iter()
+                .map(|client| client.flags.is_last_focused)
+                .collect::<Vec<_>>(),
+            [false, true, false]
+        );
+        assert_eq!(
+            test_clients_string(&renamer, clients, &config.format),
+            "code _mpv_ term"
+        );
+
+        // Without client_last_focused, the last focused client is deduplicated as usual
+        config.format.client_last_focused = None;
+        config.format.dedup = true;
+        let clients = marked(vec![
+            client("kitty", 1, ClientFlags::default()),
+            client("kitty", 2, ClientFlags::default()),
+        ]);
+        assert_eq!(
+            test_clients_string(&renamer, clients, &config.format),
+            "kitty²"
+        );
+    }
+
+    #[test]
+    fn test_empty_workspaces_are_listed() {
+        let config = crate::config::read_config_file(None, false, false).unwrap();
+        let renamer = test_renamer(&config);
+
+        let hyprland_workspaces = HyprlandWorkspaces {
+            workspaces: HashMap::from([
+                (4, hyprland_workspace(4, "DP-1")),
+                (7, hyprland_workspace(7, "DP-1")),
+            ]),
+            ..HyprlandWorkspaces::default()
+        };
+
+        let mut workspaces = renamer
+            .get_workspaces_from_clients(vec![], "0".to_string(), &hyprland_workspaces, &config)
+            .unwrap();
+        workspaces.sort_by_key(|workspace| workspace.id);
+
+        assert_eq!(
+            workspaces
+                .iter()
+                .map(|w| (w.id, w.monitor.as_str(), w.clients.len()))
+                .collect::<Vec<_>>(),
+            [(4, "DP-1", 0), (7, "DP-1", 0)]
+        );
+        assert_eq!(
+            workspace_name(&workspaces[0], "", &config.format, &config.workspaces_name),
+            "4"
+        );
+    }
+
+    #[test]
+    fn test_workspaces_name_ranges_and_names() {
+        let mut config = crate::config::read_config_file(None, false, false).unwrap();
+        config.format.workspace_empty = "{name}".to_string();
+        let workspaces_name = crate::config::generate_workspaces_name_config(&toml::toml! {
+            "1..5" = "work"
+            "11..20" = "{id|sub(10)}"
+            "3" = "three"
+            "code|dev" = "</>"
+        });
+        let name = |id: i32, name: &str| {
+            let workspace = AppWorkspace {
+                name: name.to_string(),
+                ..AppWorkspace::new(id, vec![])
+            };
+            format_workspace_name(&workspace, "", &config.format, &workspaces_name).unwrap()
+        };
+
+        assert_eq!(name(2, "2"), "work");
+        // Exact ids win over ranges
+        assert_eq!(name(3, "3"), "three");
+        assert_eq!(name(13, "13"), "3");
+        assert_eq!(name(6, "code"), "</>");
+        assert_eq!(name(7, "codex"), "7");
+    }
+
     #[test]
     fn test_workspaces_name_config() {
         let mut config = crate::config::read_config_file(None, false, false).unwrap();
 
         config
             .workspaces_name
-            .push(("0".to_string(), "zero".to_string()));
+            .push((WorkspaceKey::Id(0), "zero".to_string()));
 
         config
             .workspaces_name
-            .push(("1".to_string(), "one".to_string()));
+            .push((WorkspaceKey::Id(1), "one".to_string()));
 
         let expected = "zero".to_string();
-        let actual = get_workspace_name(0, &config.workspaces_name);
+        let actual = get_workspace_name(&AppWorkspace::new(0, vec![]), &config.workspaces_name);
 
         assert_eq!(actual, expected);
 
         let expected = "one".to_string();
-        let actual = get_workspace_name(1, &config.workspaces_name);
+        let actual = get_workspace_name(&AppWorkspace::new(1, vec![]), &config.workspaces_name);
 
         assert_eq!(actual, expected);
 
         let expected = "3".to_string();
-        let actual = get_workspace_name(3, &config.workspaces_name);
+        let actual = get_workspace_name(&AppWorkspace::new(3, vec![]), &config.workspaces_name);
 
         assert_eq!(actual, expected);
     }
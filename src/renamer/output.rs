@@ -0,0 +1,331 @@
+use crate::config::{ConfigFormatRaw, ConfigOutput, OutputEncoding, OutputKind, WorkspaceKey};
+use crate::renamer::{workspace_name, AppWorkspace, Renamer};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    pub workspaces: Vec<WorkspaceState>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceState {
+    pub id: i32,
+    pub name: String,
+    pub clients: Vec<ClientState>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClientState {
+    pub class: String,
+    pub title: String,
+    pub is_active: bool,
+}
+
+impl Renamer {
+    /// Writes the workspaces state to a non hyprland-rename output,
+    /// if it changed since the last write on this output.
+    pub fn write_output(
+        &self,
+        index: usize,
+        output: &ConfigOutput,
+        state: &State,
+    ) -> Result<(), Box<dyn Error + '_>> {
+        let snapshot = match (output.kind, output.encoding) {
+            (OutputKind::StdoutJson, _) | (_, OutputEncoding::Json) => {
+                serde_json::to_string(state)?
+            }
+            (_, OutputEncoding::Toml) => toml::to_string(state)?,
+        };
+
+        if self.output_cache.lock()?.get(&index) == Some(&snapshot) {
+            return Ok(());
+        }
+
+        let is_written = match (output.kind, &output.path) {
+            (OutputKind::StdoutJson, _) => {
+                let mut stdout = io::stdout().lock();
+                writeln!(stdout, "{snapshot}")?;
+                stdout.flush()?;
+                true
+            }
+            (OutputKind::File, Some(path)) => {
+                create_parent_dir(path)?;
+                write_file_atomic(path, &snapshot)?;
+                true
+            }
+            (OutputKind::Fifo, Some(path)) => {
+                create_parent_dir(path)?;
+                write_fifo(path, &snapshot)?
+            }
+            #[cfg(feature = "dbus")]
+            (OutputKind::Dbus, _) => {
+                self.publish_dbus(state)?;
+                true
+            }
+            (_, _) => false,
+        };
+
+        // Written again on the next change, for a fifo reader attaching later
+        if is_written {
+            self.output_cache.lock()?.insert(index, snapshot);
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the state of all workspaces, sorted by id, as seen by an output.
+pub fn workspaces_state(
+    workspaces: &[AppWorkspace],
+    workspaces_strings: &HashMap<i32, String>,
+    config_format: &ConfigFormatRaw,
+    workspaces_name: &[(WorkspaceKey, String)],
+) -> State {
+    let mut state: Vec<WorkspaceState> = workspaces
+        .iter()
+        .map(|workspace| {
+            let clients = workspaces_strings
+                .get(&workspace.id)
+                .map_or("", |s| s.as_str());
+
+            WorkspaceState {
+                id: workspace.id,
+                name: workspace_name(workspace, clients, config_format, workspaces_name),
+                clients: workspace
+                    .clients
+                    .iter()
+                    .map(|client| ClientState {
+                        class: client.class.clone(),
+                        title: client.title.clone(),
+                        is_active: client.is_active,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    state.sort_by_key(|workspace| workspace.id);
+    State { workspaces: state }
+}
+
+/// Writes the file next to its destination then moves it,
+/// so readers never see a partially written state.
+fn write_file_atomic(path: &Path, snapshot: &str) -> io::Result<()> {
+    // One temporary file per daemon, so two instances never write the same one
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(".{}.tmp", process::id()));
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut file = File::create(&tmp_path)?;
+    writeln!(file, "{snapshot}")?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+fn create_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir),
+        None => Ok(()),
+    }
+}
+
+/// Writes the state in the fifo, creating it if needed, without waiting for a reader.
+/// The state is dropped when the reader is too late to take the whole line,
+/// so it never gets a truncated one and the event loop never blocks.
+/// Returns whether the state was written.
+fn write_fifo(path: &Path, snapshot: &str) -> io::Result<bool> {
+    let open_fifo = || {
+        OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+    };
+
+    let fifo = match open_fifo() {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            create_fifo(path)?;
+            open_fifo()
+        }
+        fifo => fifo,
+    };
+
+    match fifo {
+        Ok(mut fifo) => {
+            let line = format!("{snapshot}\n");
+            if pipe_room(&fifo)? < line.len() {
+                return Ok(false);
+            }
+            fifo.write_all(line.as_bytes()).map(|_| true)
+        }
+        // Nobody is reading the fifo for now
+        Err(e) if e.raw_os_error() == Some(libc::ENXIO) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// The free space of the pipe, the daemon being its only writer.
+fn pipe_room(fifo: &File) -> io::Result<usize> {
+    let fd = fifo.as_raw_fd();
+    // SAFETY: fd is a valid pipe descriptor owned by fifo for the whole call.
+    let size = unsafe { libc::fcntl(fd, libc::F_GETPIPE_SZ) };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut queued: libc::c_int = 0;
+    // SAFETY: same as above, FIONREAD writes a c_int in queued.
+    if unsafe { libc::ioctl(fd, libc::FIONREAD, &mut queued) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((size as usize).saturating_sub(queued as usize))
+}
+
+fn create_fifo(path: &Path) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: c_path is a valid nul-terminated string for the whole call.
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renamer::tests::test_renamer;
+    use crate::renamer::IconConfig::*;
+    use crate::renamer::IconStatus::*;
+    use crate::renamer::{AppClient, ClientFlags};
+    use hyprland::data::FullscreenMode;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_write_file_output() {
+        let config = crate::config::read_config_file(None, false, false).unwrap();
+        // The directory is created on the first write
+        let dir = PathBuf::from("/tmp/hyprland-autoname-workspaces-test");
+        _ = fs::remove_dir_all(&dir);
+        let path = dir.join("state.json");
+        let output = ConfigOutput {
+            kind: OutputKind::File,
+            path: Some(path.clone()),
+            encoding: OutputEncoding::Json,
+            format: config.format.clone(),
+        };
+
+        let renamer = test_renamer(&config);
+
+        let workspaces = vec![
+            AppWorkspace::new(2, vec![]),
+            AppWorkspace::new(
+                1,
+                vec![AppClient {
+                    initial_class: "kitty".to_string(),
+                    class: "kitty".to_string(),
+                    title: "zsh".to_string(),
+                    initial_title: "zsh".to_string(),
+                    is_active: true,
+                    is_fullscreen: FullscreenMode::None,
+                    matched_rule: Active(Class("kitty".to_string(), "term".to_string(), None)),
+                    flags: ClientFlags::default(),
+                }],
+            ),
+        ];
+
+        let strings = renamer.generate_workspaces_string(workspaces.clone(), &output.format);
+        let state = workspaces_state(
+            &workspaces,
+            &strings,
+            &output.format,
+            &config.workspaces_name,
+        );
+
+        assert_eq!(state.workspaces.len(), 2);
+        assert_eq!(state.workspaces[0].id, 1);
+        assert_eq!(state.workspaces[0].name, "1: term");
+        assert_eq!(state.workspaces[0].clients[0].class, "kitty");
+        assert_eq!(state.workspaces[1].name, "2");
+
+        renamer.write_output(0, &output, &state).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert_eq!(written.trim(), serde_json::to_string(&state).unwrap());
+
+        // Unchanged state is not written again
+        fs::remove_file(&path).unwrap();
+        renamer.write_output(0, &output, &state).unwrap();
+        assert!(!path.exists());
+
+        let output = ConfigOutput {
+            encoding: OutputEncoding::Toml,
+            ..output
+        };
+        renamer.write_output(1, &output, &state).unwrap();
+        let written: toml::Table = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["workspaces"][0]["name"].as_str(), Some("1: term"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_fifo_output() {
+        let path = PathBuf::from("/tmp/hyprland-autoname-workspaces-test-state.fifo");
+        _ = fs::remove_file(&path);
+
+        // Without reader, the fifo is created and nothing blocks
+        assert!(!write_fifo(&path, "{}").unwrap());
+        assert!(path.exists());
+
+        // Nor cached, a reader attaching later gets the state
+        let config = crate::config::read_config_file(None, false, false).unwrap();
+        let output = ConfigOutput {
+            kind: OutputKind::Fifo,
+            path: Some(path.clone()),
+            encoding: OutputEncoding::Json,
+            format: config.format.clone(),
+        };
+        let renamer = test_renamer(&config);
+        let state = State { workspaces: vec![] };
+        renamer.write_output(0, &output, &state).unwrap();
+        assert!(renamer.output_cache.lock().unwrap().is_empty());
+
+        let reader = {
+            let path = path.clone();
+            std::thread::spawn(move || fs::read_to_string(path).unwrap())
+        };
+
+        // Push until the blocking reader opened the fifo and got the state
+        while !reader.is_finished() {
+            renamer.write_output(0, &output, &state).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let read = reader.join().unwrap();
+        assert_eq!(read.lines().next(), Some("{\"workspaces\":[]}"));
+
+        // A reader not reading never blocks the writes, and only gets whole lines
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .unwrap();
+        let snapshot = "x".repeat(10_000);
+        let written = (0..20)
+            .filter(|_| write_fifo(&path, &snapshot).unwrap())
+            .count();
+        assert!(written > 0 && written < 20);
+        let mut read = String::new();
+        io::Read::read_to_string(&mut reader, &mut read).unwrap_or_default();
+        assert_eq!(read.lines().count(), written);
+        assert!(read.lines().all(|line| line == snapshot));
+        fs::remove_file(&path).unwrap();
+    }
+}